@@ -0,0 +1,108 @@
+/// Plays a [`crate::Strategy`] as [`client::UI`], without any display. The
+/// results of its shots are passed on to the strategy, as soon as they are
+/// known to the client.
+pub struct Bot<S> {
+    strategy: S,
+    accept_rematches: bool,
+    shots: Shots,
+}
+
+impl<S: crate::Strategy> Bot<S> {
+    /// A bot declining every rematch
+    pub fn new(strategy: S) -> Bot<S> {
+        Bot {
+            strategy,
+            accept_rematches: false,
+            shots: Shots::default(),
+        }
+    }
+
+    pub fn accept_rematches(mut self, accept: bool) -> Bot<S> {
+        self.accept_rematches = accept;
+        self
+    }
+}
+
+impl<S: crate::Strategy> client::UI for Bot<S> {
+    type Error = std::convert::Infallible;
+
+    async fn request_ships(&mut self) -> Result<logic::Ships, Self::Error> {
+        self.shots = Shots::default();
+        Ok(self.strategy.place_ships())
+    }
+
+    async fn request_target(
+        &mut self,
+        info: client::ui::ClientInfo<'_>,
+    ) -> Result<client::ui::Target, Self::Error> {
+        if let Some((target, result)) = self.shots.result(info) {
+            self.strategy.observe(target, result);
+        }
+        let target = self.strategy.choose_target();
+        self.shots.fire(target);
+        Ok(client::ui::Target::Position(target))
+    }
+
+    fn display_board(
+        &mut self,
+        _: client::ui::ClientInfo,
+        _: &client::ClientEvent,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn next_command(
+        &mut self,
+        _: client::ui::ClientInfo<'_>,
+    ) -> Result<client::ui::Command, Self::Error> {
+        std::future::pending().await
+    }
+
+    async fn request_rematch(
+        &mut self,
+        _: client::ui::ClientInfo<'_>,
+    ) -> Result<bool, Self::Error> {
+        Ok(self.accept_rematches)
+    }
+
+    async fn display_victory(&mut self, _: client::ui::ClientInfo<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn display_loss(&mut self, _: client::ui::ClientInfo<'_>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Learns the results of the shots of a bot from the [`client::ui::ClientInfo`]
+#[derive(Debug, Default)]
+pub(crate) struct Shots {
+    /// The last target, until its result is known
+    pending: Option<logic::Position>,
+    /// Opponent ships already known to be sunk
+    sunk: usize,
+}
+
+impl Shots {
+    pub(crate) fn fire(&mut self, target: logic::Position) {
+        self.pending = Some(target);
+    }
+
+    /// The result of the last target, once the server has answered it
+    pub(crate) fn result(
+        &mut self,
+        info: client::ui::ClientInfo,
+    ) -> Option<(logic::Position, logic::board::AttackInfo)> {
+        let target = self.pending?;
+        let result = match info.opponent_hit_map[target]? {
+            client::AttackInfo::Miss => logic::board::AttackInfo::Miss,
+            client::AttackInfo::Hit => {
+                let sunk = info.opponent_ships.get(self.sunk).copied();
+                self.sunk = info.opponent_ships.len();
+                logic::board::AttackInfo::Hit(sunk)
+            }
+        };
+        self.pending = None;
+        Some((target, result))
+    }
+}
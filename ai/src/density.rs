@@ -0,0 +1,115 @@
+use rand::{rngs::StdRng, seq::IndexedRandom};
+
+/// What is known about a position of the opponent's board, `None` while it
+/// was not shot at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Miss,
+    /// Part of a ship that is still afloat
+    Hit,
+    /// Part of a sunk ship
+    Sunk,
+}
+
+/// Weight of a placement for every hit it explains, so finishing a ship beats
+/// searching for the next one
+const HIT_WEIGHT: u32 = 20;
+
+/// Counts for every position, in how many ways the remaining ships could be
+/// placed over it. Placements over hits count more, positions already shot at
+/// are `0`. The array is indexed like the board.
+pub fn density(cells: &[[Option<Cell>; 10]; 10], remaining: &[u8]) -> [[u32; 10]; 10] {
+    let mut density = [[0; 10]; 10];
+    for &length in remaining {
+        for plan in placements(length) {
+            let Ok(ship) = logic::ship::Ship::try_from(plan) else {
+                continue;
+            };
+            let mut hits = 0;
+            let blocked = ship.into_iter().any(|pos| match cells[pos] {
+                Some(Cell::Miss | Cell::Sunk) => true,
+                Some(Cell::Hit) => {
+                    hits += 1;
+                    false
+                }
+                None => false,
+            });
+            if blocked {
+                continue;
+            }
+            let weight = 1 + hits * HIT_WEIGHT;
+            for pos in ship.into_iter().filter(|&pos| cells[pos].is_none()) {
+                density[pos] += weight;
+            }
+        }
+    }
+    density
+}
+
+/// Every way a ship of the length may lie on the board
+fn placements(length: u8) -> impl Iterator<Item = logic::ship::ShipPlan> {
+    crate::positions().flat_map(move |pos| {
+        [
+            logic::ship::ShipPlan::Horizontal { pos, length },
+            logic::ship::ShipPlan::Vertical { pos, length },
+        ]
+    })
+}
+
+/// Shoots where the remaining ships most likely are, according to [`density`]
+pub struct Density {
+    rng: StdRng,
+    cells: [[Option<Cell>; 10]; 10],
+    /// Lengths of the ships still afloat
+    remaining: Vec<u8>,
+}
+
+impl Density {
+    pub fn new(rng: StdRng) -> Density {
+        Density {
+            rng,
+            cells: [[None; 10]; 10],
+            remaining: logic::ship::SHIP_LENGTHS.to_vec(),
+        }
+    }
+}
+
+impl crate::Strategy for Density {
+    fn place_ships(&mut self) -> logic::Ships {
+        self.cells = [[None; 10]; 10];
+        self.remaining = logic::ship::SHIP_LENGTHS.to_vec();
+        crate::random_ships(&mut self.rng)
+    }
+
+    fn choose_target(&mut self) -> logic::Position {
+        let density = density(&self.cells, &self.remaining);
+        let untried: Vec<_> = crate::positions()
+            .filter(|&pos| self.cells[pos].is_none())
+            .collect();
+        let best = untried.iter().map(|&pos| density[pos]).max().unwrap_or(0);
+        let candidates: Vec<_> = untried
+            .into_iter()
+            .filter(|&pos| density[pos] == best)
+            .collect();
+
+        *candidates
+            .choose(&mut self.rng)
+            .expect("the match ends before every position is shot at")
+    }
+
+    fn observe(&mut self, target: logic::Position, result: logic::board::AttackInfo) {
+        match result {
+            logic::board::AttackInfo::Miss => self.cells[target] = Some(Cell::Miss),
+            logic::board::AttackInfo::Hit(None) => self.cells[target] = Some(Cell::Hit),
+            logic::board::AttackInfo::Hit(Some(ship)) => {
+                for pos in ship {
+                    self.cells[pos] = Some(Cell::Sunk);
+                }
+                let length = ship.into_iter().count() as u8;
+                if let Some(i) = self.remaining.iter().position(|&l| l == length) {
+                    self.remaining.swap_remove(i);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! A line based protocol, letting bots written in any language play as
+//! client. The bot runs as separate process, reading commands from stdin and
+//! answering on stdout, one line each. Positions are written as on the board,
+//! `A1` to `J10`, ships as their first and last position, like `B2-B6`.
+//!
+//! | command                    | answer                               |
+//! |----------------------------|--------------------------------------|
+//! | `ziel 1`                   | `ready`                              |
+//! | `place`                    | five ships, separated by spaces      |
+//! | `target`                   | a position not shot at in this match |
+//! | `result <pos> miss`        | -                                    |
+//! | `result <pos> hit`         | -                                    |
+//! | `result <pos> sunk <ship>` | -                                    |
+//! | `victory` / `loss`         | -                                    |
+//!
+//! Every match starts with `place`. The result of a shot is told before the
+//! next `target`, the outcome when the connection ends or before a rematch.
+//! Unknown commands should be ignored. Once stdin is closed, the bot has to
+//! exit.
+
+use std::{process::Stdio, time::Duration};
+
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncWriteExt},
+    process, time,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("ai :: engine :: {0}")]
+    Io(#[from] io::Error),
+    #[error("ai :: engine :: no answer to `{0}` within {1:?}")]
+    Timeout(&'static str, Duration),
+    #[error("ai :: engine :: bot exited")]
+    Exited,
+    #[error("ai :: engine :: invalid answer to `{0}` :: {1}")]
+    InvalidAnswer(&'static str, String),
+}
+
+type Input = Box<dyn io::AsyncWrite + Send + Unpin>;
+type Output = io::Lines<io::BufReader<Box<dyn io::AsyncRead + Send + Unpin>>>;
+
+/// Plays a bot running as separate process as [`client::UI`]. A bot not
+/// answering in time is given up on, as its late answer would be taken for
+/// the next one.
+///
+/// Cancelled requests are resumed by the next one: every line is sent once,
+/// and a command is not asked again while its answer is outstanding.
+pub struct Engine {
+    child: Option<process::Child>,
+    /// `None` once the bot is given up on
+    input: Option<Input>,
+    output: Output,
+    /// Lines not yet taken by the bot
+    unsent: Vec<u8>,
+    /// The command waiting for its answer, and until when the bot may take
+    asked: Option<(&'static str, time::Instant)>,
+    /// How long the bot may take to answer a command
+    timeout: Duration,
+    accept_rematches: bool,
+    shots: crate::bot::Shots,
+    /// Whether the bot was told the outcome of the current match
+    outcome_told: bool,
+}
+
+impl Engine {
+    /// Starts the bot and waits for it to greet back
+    pub async fn spawn(command: &mut process::Command, timeout: Duration) -> Result<Engine, Error> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let (input, output) = (
+            child.stdin.take().expect("stdin is piped"),
+            child.stdout.take().expect("stdout is piped"),
+        );
+        let mut engine = Engine::new(input, output, timeout);
+        engine.child = Some(child);
+        engine.greet().await?;
+        Ok(engine)
+    }
+
+    /// Talks to a bot over the given pipes, see [`serve`]
+    pub async fn connect(
+        input: impl io::AsyncWrite + Send + Unpin + 'static,
+        output: impl io::AsyncRead + Send + Unpin + 'static,
+        timeout: Duration,
+    ) -> Result<Engine, Error> {
+        let mut engine = Engine::new(input, output, timeout);
+        engine.greet().await?;
+        Ok(engine)
+    }
+
+    /// An engine declining every rematch
+    fn new(
+        input: impl io::AsyncWrite + Send + Unpin + 'static,
+        output: impl io::AsyncRead + Send + Unpin + 'static,
+        timeout: Duration,
+    ) -> Engine {
+        let output: Box<dyn io::AsyncRead + Send + Unpin> = Box::new(output);
+        Engine {
+            child: None,
+            input: Some(Box::new(input)),
+            output: io::BufReader::new(output).lines(),
+            unsent: vec![],
+            asked: None,
+            timeout,
+            accept_rematches: false,
+            shots: crate::bot::Shots::default(),
+            outcome_told: false,
+        }
+    }
+
+    pub fn accept_rematches(mut self, accept: bool) -> Engine {
+        self.accept_rematches = accept;
+        self
+    }
+
+    async fn greet(&mut self) -> Result<(), Error> {
+        match self.ask("ziel 1").await?.as_str() {
+            "ready" => Ok(()),
+            answer => Err(Error::InvalidAnswer("ziel 1", answer.to_string())),
+        }
+    }
+
+    /// Queues the line to be sent with the next command, or [`Engine::flush`]
+    fn queue(&mut self, line: &str) {
+        self.unsent
+            .extend_from_slice(format!("{line}\n").as_bytes());
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        write_unsent(&mut self.input, &mut self.unsent).await
+    }
+
+    /// Sends the command, unless a cancelled request already has. An
+    /// outstanding answer to another command is read and dropped first.
+    async fn ask(&mut self, command: &'static str) -> Result<String, Error> {
+        loop {
+            let (asked, deadline) = match self.asked {
+                Some(asked) => asked,
+                None => {
+                    self.queue(command);
+                    *self
+                        .asked
+                        .insert((command, time::Instant::now() + self.timeout))
+                }
+            };
+            let answer = time::timeout_at(deadline, async {
+                write_unsent(&mut self.input, &mut self.unsent).await?;
+                Ok(self.output.next_line().await?)
+            });
+            match answer.await {
+                Ok(Ok(Some(line))) => {
+                    self.asked = None;
+                    if asked == command {
+                        return Ok(line.trim().to_string());
+                    }
+                }
+                Ok(Ok(None)) => return Err(Error::Exited),
+                Ok(Err(err)) => return Err(err),
+                Err(_) => {
+                    // every later command fails as if the bot exited
+                    self.input = None;
+                    self.asked = None;
+                    if let Some(child) = &mut self.child {
+                        let _ = child.start_kill();
+                    }
+                    return Err(Error::Timeout(asked, self.timeout));
+                }
+            }
+        }
+    }
+
+    /// Closes stdin, which asks the bot to exit, and waits for it
+    pub async fn close(mut self) {
+        self.input = None;
+        if let Some(child) = self.child.take() {
+            stop(child, self.timeout).await
+        }
+    }
+
+    /// Tells the outcome once per match, however often it is shown
+    async fn tell_outcome(&mut self, info: client::ui::ClientInfo<'_>) -> Result<(), Error> {
+        if let (Some(victory), false) = (info.outcome, self.outcome_told) {
+            self.outcome_told = true;
+            self.queue(if victory { "victory" } else { "loss" });
+        }
+        self.flush().await
+    }
+}
+
+/// Writes as much of `unsent` as the bot takes, dropping what was written.
+/// Dropping the returned future loses no line.
+async fn write_unsent(input: &mut Option<Input>, unsent: &mut Vec<u8>) -> Result<(), Error> {
+    if unsent.is_empty() {
+        return Ok(());
+    }
+    let input = input.as_mut().ok_or(Error::Exited)?;
+    let written = async {
+        while !unsent.is_empty() {
+            let written = input.write(unsent).await?;
+            if written == 0 {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            unsent.drain(..written);
+        }
+        input.flush().await
+    };
+    written.await.map_err(|err| match err.kind() {
+        io::ErrorKind::BrokenPipe => Error::Exited,
+        _ => Error::Io(err),
+    })
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.input = None;
+        let Some(child) = self.child.take() else {
+            return;
+        };
+        // without a runtime, the child gets killed right away as it is dropped
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(stop(child, self.timeout));
+        }
+    }
+}
+
+/// Waits for the bot to exit, killing it if it takes longer than `timeout`
+async fn stop(mut child: process::Child, timeout: Duration) {
+    if time::timeout(timeout, child.wait()).await.is_err() {
+        let _ = child.kill().await;
+    }
+}
+
+impl client::UI for Engine {
+    type Error = Error;
+
+    async fn request_ships(&mut self) -> Result<logic::Ships, Self::Error> {
+        self.shots = crate::bot::Shots::default();
+        self.outcome_told = false;
+        let answer = self.ask("place").await?;
+        answer
+            .split_whitespace()
+            .map(|ship| ship.parse().ok())
+            .collect::<Option<Vec<_>>>()
+            .and_then(|ships| <[_; 5]>::try_from(ships).ok())
+            .and_then(|ships| logic::Ships::try_from(ships).ok())
+            .ok_or(Error::InvalidAnswer("place", answer))
+    }
+
+    async fn request_target(
+        &mut self,
+        info: client::ui::ClientInfo<'_>,
+    ) -> Result<client::ui::Target, Self::Error> {
+        // the result is only known once, so it is queued before anything
+        // could cancel the request
+        if let Some((target, result)) = self.shots.result(info) {
+            self.queue(&match result {
+                logic::board::AttackInfo::Miss => format!("result {target} miss"),
+                logic::board::AttackInfo::Hit(None) => format!("result {target} hit"),
+                logic::board::AttackInfo::Hit(Some(ship)) => {
+                    format!("result {target} sunk {ship}")
+                }
+            });
+        }
+        let answer = self.ask("target").await?;
+        let target = answer
+            .parse::<logic::Position>()
+            .ok()
+            .filter(|&target| info.opponent_hit_map[target].is_none())
+            .ok_or(Error::InvalidAnswer("target", answer))?;
+        self.shots.fire(target);
+        Ok(client::ui::Target::Position(target))
+    }
+
+    fn display_board(
+        &mut self,
+        _: client::ui::ClientInfo,
+        _: &client::ClientEvent,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn next_command(
+        &mut self,
+        _: client::ui::ClientInfo<'_>,
+    ) -> Result<client::ui::Command, Self::Error> {
+        std::future::pending().await
+    }
+
+    async fn request_rematch(
+        &mut self,
+        info: client::ui::ClientInfo<'_>,
+    ) -> Result<bool, Self::Error> {
+        self.tell_outcome(info).await?;
+        Ok(self.accept_rematches)
+    }
+
+    async fn display_victory(
+        &mut self,
+        info: client::ui::ClientInfo<'_>,
+    ) -> Result<(), Self::Error> {
+        self.tell_outcome(info).await
+    }
+
+    async fn display_loss(&mut self, info: client::ui::ClientInfo<'_>) -> Result<(), Self::Error> {
+        self.tell_outcome(info).await
+    }
+}
+
+/// Plays `strategy` as bot, answering the commands read from `input` until it
+/// ends. Any built-in strategy can be run this way, as reference for bots in
+/// other languages.
+pub async fn serve(
+    strategy: &mut (impl crate::Strategy + ?Sized),
+    input: impl io::AsyncBufRead + Unpin,
+    mut output: impl io::AsyncWrite + Unpin,
+) -> Result<(), io::Error> {
+    let mut lines = input.lines();
+    while let Some(line) = lines.next_line().await? {
+        let answer = match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["ziel", "1"] => "ready".to_string(),
+            ["place"] => strategy
+                .place_ships()
+                .into_iter()
+                .map(|ship| ship.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            ["target"] => strategy.choose_target().to_string(),
+            ["result", target, ref result @ ..] => {
+                let result = match result {
+                    ["miss"] => Some(logic::board::AttackInfo::Miss),
+                    ["hit"] => Some(logic::board::AttackInfo::Hit(None)),
+                    ["sunk", ship] => ship
+                        .parse()
+                        .ok()
+                        .map(|ship| logic::board::AttackInfo::Hit(Some(ship))),
+                    _ => None,
+                };
+                if let (Some(target), Some(result)) = (target.parse().ok(), result) {
+                    strategy.observe(target, result);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        output.write_all(format!("{answer}\n").as_bytes()).await?;
+        output.flush().await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use rand::{rngs::StdRng, seq::IndexedRandom};
+
+use crate::density::Cell;
+
+/// Hunts on a checkerboard pattern, which every ship overlaps. After a hit,
+/// it targets the neighbours until the ship is sunk, following the line of
+/// hits where there is one.
+pub struct Hunt {
+    rng: StdRng,
+    cells: [[Option<Cell>; 10]; 10],
+}
+
+impl Hunt {
+    pub fn new(rng: StdRng) -> Hunt {
+        Hunt {
+            rng,
+            cells: [[None; 10]; 10],
+        }
+    }
+
+    /// How promising an untried position next to a hit is. `0` if it is not
+    /// next to a hit.
+    fn score(&self, pos: logic::Position) -> u8 {
+        let (x, y) = pos.to_coords();
+        let cell = |dx: i8, dy: i8| {
+            let coords = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+            self.cells[logic::Position::try_from_coords(coords).ok()?]
+        };
+        let hit = |dx, dy| cell(dx, dy) == Some(Cell::Hit);
+
+        [(1, 0), (-1, 0), (0, 1), (0, -1)]
+            .into_iter()
+            .filter(|&(dx, dy)| hit(dx, dy))
+            // continuing a line of hits beats a lone neighbour
+            .map(|(dx, dy)| if hit(dx * 2, dy * 2) { 2 } else { 1 })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl crate::Strategy for Hunt {
+    fn place_ships(&mut self) -> logic::Ships {
+        self.cells = [[None; 10]; 10];
+        crate::random_ships(&mut self.rng)
+    }
+
+    fn choose_target(&mut self) -> logic::Position {
+        let untried: Vec<_> = crate::positions()
+            .filter(|&pos| self.cells[pos].is_none())
+            .collect();
+
+        let best = untried
+            .iter()
+            .map(|&pos| self.score(pos))
+            .max()
+            .unwrap_or(0);
+        let candidates: Vec<_> = if best > 0 {
+            untried
+                .into_iter()
+                .filter(|&pos| self.score(pos) == best)
+                .collect()
+        } else {
+            let (checkerboard, rest): (Vec<_>, Vec<_>) = untried.into_iter().partition(|pos| {
+                let (x, y) = pos.to_coords();
+                (x + y) % 2 == 0
+            });
+            if checkerboard.is_empty() {
+                rest
+            } else {
+                checkerboard
+            }
+        };
+
+        *candidates
+            .choose(&mut self.rng)
+            .expect("the match ends before every position is shot at")
+    }
+
+    fn observe(&mut self, target: logic::Position, result: logic::board::AttackInfo) {
+        match result {
+            logic::board::AttackInfo::Miss => self.cells[target] = Some(Cell::Miss),
+            logic::board::AttackInfo::Hit(None) => self.cells[target] = Some(Cell::Hit),
+            logic::board::AttackInfo::Hit(Some(ship)) => {
+                for pos in ship {
+                    self.cells[pos] = Some(Cell::Sunk);
+                }
+            }
+        }
+    }
+}
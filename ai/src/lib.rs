@@ -0,0 +1,566 @@
+use rand::{Rng, SeedableRng};
+
+#[cfg(feature = "client")]
+mod bot;
+pub mod density;
+#[cfg(feature = "client")]
+pub mod engine;
+mod hunt;
+mod random;
+pub mod simulate;
+
+#[cfg(feature = "client")]
+pub use bot::Bot;
+pub use density::Density;
+#[cfg(feature = "client")]
+pub use engine::Engine;
+pub use hunt::Hunt;
+pub use random::Random;
+
+/// A computer player. Every match starts with [`Strategy::place_ships`],
+/// afterwards the strategy gets asked for targets and told what they hit.
+/// Wrapped in a [`Bot`], it plays on a server like any other client.
+pub trait Strategy {
+    /// Places the fleet for a new match, forgetting the previous one
+    fn place_ships(&mut self) -> logic::Ships;
+    /// Picks the next position to shoot at, which was not shot at before in
+    /// this match
+    fn choose_target(&mut self) -> logic::Position;
+    /// Learns what the shot at `target` hit
+    fn observe(&mut self, target: logic::Position, result: logic::board::AttackInfo);
+}
+
+impl<S: Strategy + ?Sized> Strategy for Box<S> {
+    fn place_ships(&mut self) -> logic::Ships {
+        (**self).place_ships()
+    }
+
+    fn choose_target(&mut self) -> logic::Position {
+        (**self).choose_target()
+    }
+
+    fn observe(&mut self, target: logic::Position, result: logic::board::AttackInfo) {
+        (**self).observe(target, result)
+    }
+}
+
+/// Names of the built-in strategies, see [`by_name`]
+pub const STRATEGIES: [&str; 3] = ["random", "hunt", "density"];
+
+/// A built-in strategy, whose random choices follow from the seed
+pub fn by_name(name: &str, seed: u64) -> Option<Box<dyn Strategy + Send>> {
+    let rng = rand::rngs::StdRng::seed_from_u64(seed);
+    match name {
+        "random" => Some(Box::new(Random::new(rng))),
+        "hunt" => Some(Box::new(Hunt::new(rng))),
+        "density" => Some(Box::new(Density::new(rng))),
+        _ => None,
+    }
+}
+
+/// A fleet placed at random
+pub fn random_ships(rng: &mut impl Rng) -> logic::Ships {
+    loop {
+        let ships = logic::ship::SHIP_LENGTHS.map(|length| {
+            let plan = if rng.random_bool(0.5) {
+                logic::ship::ShipPlan::Horizontal {
+                    pos: random_position(rng, 10 - length, 9),
+                    length,
+                }
+            } else {
+                logic::ship::ShipPlan::Vertical {
+                    pos: random_position(rng, 9, 10 - length),
+                    length,
+                }
+            };
+            logic::ship::Ship::try_from(plan).expect("ship is placed within the board")
+        });
+        // overlapping fleets get placed again
+        if let Ok(ships) = logic::Ships::try_from(ships) {
+            return ships;
+        }
+    }
+}
+
+/// A position with coordinates up to the given maximums
+fn random_position(rng: &mut impl Rng, max_x: u8, max_y: u8) -> logic::Position {
+    logic::Position::try_from_coords((rng.random_range(0..=max_x), rng.random_range(0..=max_y)))
+        .expect("maximums are within the board")
+}
+
+/// Every position of the board
+fn positions() -> impl Iterator<Item = logic::Position> {
+    (0..10).flat_map(|x| {
+        (0..10).map(move |y| {
+            logic::Position::try_from_coords((x, y)).expect("coordinates are within the board")
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategies_sink_every_fleet() {
+        for name in STRATEGIES {
+            for seed in 0..20 {
+                let mut strategy = by_name(name, seed).unwrap();
+                let mut board = logic::Board::from_ships(random_ships(
+                    &mut rand::rngs::StdRng::seed_from_u64(seed + 100),
+                ));
+                strategy.place_ships();
+
+                let mut shots = 0;
+                while !board.is_all_sunken() {
+                    let target = strategy.choose_target();
+                    let result = board.target(target).expect("no position is shot twice");
+                    strategy.observe(target, result);
+                    shots += 1;
+                }
+                assert!(shots <= 100);
+            }
+        }
+    }
+
+    #[test]
+    fn density_follows_hits() {
+        let pos = |coords| logic::Position::try_from_coords(coords).unwrap();
+        let mut cells = [[None; 10]; 10];
+        cells[pos((4, 4))] = Some(density::Cell::Hit);
+        cells[pos((0, 0))] = Some(density::Cell::Miss);
+        let density = density::density(&cells, &[2]);
+
+        assert_eq!(density[pos((4, 4))], 0);
+        assert_eq!(density[pos((0, 0))], 0);
+        let best = crate::positions().map(|p| density[p]).max().unwrap();
+        for neighbour in [(3, 4), (5, 4), (4, 3), (4, 5)] {
+            assert_eq!(density[pos(neighbour)], best);
+        }
+        assert!(density[pos((1, 0))] < density[pos((2, 0))]);
+        assert_eq!(density::density(&cells, &[]), [[0; 10]; 10]);
+    }
+
+    #[test]
+    fn simulation_is_seeded() {
+        let report = simulate::run(["hunt", "random"], 50, 7).unwrap();
+        assert_eq!(report, simulate::run(["hunt", "random"], 50, 7).unwrap());
+        assert_eq!(report.players[0].wins + report.players[1].wins, 50);
+        assert!(report.players[0].wins > report.players[1].wins);
+
+        let median = report.players[0].percentile(50.0).unwrap();
+        assert!(report.players[0].percentile(10.0).unwrap() <= median);
+        assert!(median <= report.players[0].percentile(90.0).unwrap());
+        let (low, high) = report.players[0].win_rate_interval();
+        assert!(low <= report.players[0].win_rate() && report.players[0].win_rate() <= high);
+
+        assert!(matches!(
+            simulate::run(["hunt", "psychic"], 1, 0),
+            Err(simulate::Error::UnknownStrategy(name)) if name == "psychic"
+        ));
+    }
+
+    /// Keeps track of what the bot passes on to the strategy
+    #[cfg(feature = "client")]
+    struct Observed {
+        strategy: Hunt,
+        results: std::sync::Arc<std::sync::Mutex<Vec<logic::board::AttackInfo>>>,
+    }
+
+    #[cfg(feature = "client")]
+    impl Strategy for Observed {
+        fn place_ships(&mut self) -> logic::Ships {
+            self.strategy.place_ships()
+        }
+
+        fn choose_target(&mut self) -> logic::Position {
+            self.strategy.choose_target()
+        }
+
+        fn observe(&mut self, target: logic::Position, result: logic::board::AttackInfo) {
+            self.results.lock().unwrap().push(result);
+            self.strategy.observe(target, result)
+        }
+    }
+
+    #[cfg(feature = "client")]
+    fn heartbeat() -> protocol::session::Heartbeat {
+        protocol::session::Heartbeat::new(
+            std::time::Duration::from_secs(1),
+            std::time::Duration::from_secs(5),
+        )
+        .unwrap()
+    }
+
+    #[cfg(feature = "client")]
+    async fn start_server(computer: Option<server::computer::Config>) -> std::net::SocketAddr {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        tokio::spawn(server::listen(
+            addr,
+            server::Config {
+                best_of: None,
+                swap_first: false,
+                heartbeat: heartbeat(),
+                history: None,
+                players: None,
+                computer,
+            },
+        ));
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        addr
+    }
+
+    #[cfg(feature = "client")]
+    async fn play(
+        ui: &mut impl client::UI,
+        addr: std::net::SocketAddr,
+        name: &str,
+    ) -> Result<bool, String> {
+        let config = client::Config {
+            identity: client::Identity {
+                name: name.to_string(),
+                password: None,
+            },
+            heartbeat: heartbeat(),
+        };
+        client::play_round(ui, addr, &config)
+            .await
+            .map_err(|err| err.to_string())
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn bots_play_on_server() {
+        let addr = start_server(None).await;
+        let results = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let mut hunt = Bot::new(Observed {
+            strategy: Hunt::new(rand::rngs::StdRng::seed_from_u64(1)),
+            results: results.clone(),
+        });
+        let mut random = Bot::new(by_name("random", 2).unwrap());
+        let (hunt, random) = tokio::join!(
+            play(&mut hunt, addr, "hunt"),
+            play(&mut random, addr, "random"),
+        );
+        assert!(hunt.unwrap());
+        assert!(!random.unwrap());
+
+        let results = results.lock().unwrap();
+        let sunk = results
+            .iter()
+            .filter(|result| matches!(result, logic::board::AttackInfo::Hit(Some(_))))
+            .count();
+        // the shot sinking the last ship ends the match, before it is observed
+        assert_eq!(sunk, 4);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn events_replay_snapshot() {
+        let addr = start_server(None).await;
+        let config = client::Config {
+            identity: client::Identity {
+                name: "hunt".to_string(),
+                password: None,
+            },
+            heartbeat: heartbeat(),
+        };
+        let mut hunt = Bot::new(by_name("hunt", 1).unwrap());
+        let mut client = client::Client::handshake(&mut hunt, addr, &config)
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap();
+        let mut replay = client.snapshot().clone();
+        let mut events = client.subscribe();
+
+        let mut random = Bot::new(by_name("random", 2).unwrap());
+        let (hunt, random) = tokio::join!(
+            async { client.play(&mut hunt).await.map_err(|err| err.to_string()) },
+            play(&mut random, addr, "random"),
+        );
+        assert!(hunt.unwrap());
+        assert!(!random.unwrap());
+
+        let mut sunk = 0;
+        while let Some(event) = events.next().await {
+            if let client::ClientEvent::OpponentShipSunk(_) = event {
+                sunk += 1;
+            }
+            replay.apply(&event);
+        }
+        assert_eq!(sunk, 5);
+        assert_eq!(replay.outcome, Some(true));
+        assert_eq!(replay.opponent_ships.len(), 5);
+
+        let history = &replay.history;
+        let last = history.last().unwrap();
+        assert_eq!(last.shooter, client::ui::Side::Client);
+        assert_eq!(last.sunk, replay.opponent_ships.last().copied());
+        let hits = history.iter().filter(|turn| {
+            turn.shooter == client::ui::Side::Opponent && turn.result == client::AttackInfo::Hit
+        });
+        let fleet = replay.fleet.iter();
+        assert_eq!(
+            hits.count(),
+            fleet.map(|status| status.hits as usize).sum::<usize>()
+        );
+        for status in &replay.fleet {
+            assert_eq!(status.sunk, status.hits == status.length());
+        }
+
+        let sunk_by_client = history
+            .iter()
+            .filter(|turn| turn.shooter == client::ui::Side::Client && turn.sunk.is_some());
+        assert_eq!(sunk_by_client.count(), 5);
+        for turns in history.windows(2) {
+            let number = match turns[0].shooter == turns[1].shooter {
+                true => turns[0].number,
+                false => turns[0].number + 1,
+            };
+            assert_eq!(turns[1].number, number);
+            assert!(turns[0].elapsed <= turns[1].elapsed);
+        }
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn engine_plays_on_server() {
+        let addr = start_server(None).await;
+        let (engine_input, bot_input) = tokio::io::duplex(64);
+        let (bot_output, engine_output) = tokio::io::duplex(64);
+        let bot = tokio::spawn(async move {
+            let mut strategy = Hunt::new(rand::rngs::StdRng::seed_from_u64(1));
+            engine::serve(
+                &mut strategy,
+                tokio::io::BufReader::new(bot_input),
+                bot_output,
+            )
+            .await
+        });
+        let mut engine = Engine::connect(
+            engine_input,
+            engine_output,
+            std::time::Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        let mut random = Bot::new(by_name("random", 2).unwrap());
+        let (hunt, random) = tokio::join!(
+            play(&mut engine, addr, "engine"),
+            play(&mut random, addr, "random"),
+        );
+        assert!(hunt.unwrap());
+        assert!(!random.unwrap());
+
+        // the bot stops once its input is closed
+        drop(engine);
+        bot.await.unwrap().unwrap();
+    }
+
+    /// Plays the engine, giving up on every target request that is not
+    /// answered right away, as the client does whenever its state changes
+    #[cfg(feature = "client")]
+    struct Impatient {
+        engine: Engine,
+        cancelled: usize,
+    }
+
+    #[cfg(feature = "client")]
+    impl client::UI for Impatient {
+        type Error = engine::Error;
+
+        async fn request_ships(&mut self) -> Result<logic::Ships, Self::Error> {
+            self.engine.request_ships().await
+        }
+
+        async fn request_target(
+            &mut self,
+            info: client::ui::ClientInfo<'_>,
+        ) -> Result<client::ui::Target, Self::Error> {
+            let wait = std::time::Duration::from_millis(1);
+            match tokio::time::timeout(wait, self.engine.request_target(info)).await {
+                Ok(target) => target,
+                Err(_) => {
+                    self.cancelled += 1;
+                    // the client requests the target again
+                    Ok(client::ui::Target::Command(
+                        client::ui::Command::Leaderboard,
+                    ))
+                }
+            }
+        }
+
+        fn display_board(
+            &mut self,
+            info: client::ui::ClientInfo,
+            event: &client::ClientEvent,
+        ) -> Result<(), Self::Error> {
+            self.engine.display_board(info, event)
+        }
+
+        async fn next_command(
+            &mut self,
+            info: client::ui::ClientInfo<'_>,
+        ) -> Result<client::ui::Command, Self::Error> {
+            self.engine.next_command(info).await
+        }
+
+        async fn request_rematch(
+            &mut self,
+            info: client::ui::ClientInfo<'_>,
+        ) -> Result<bool, Self::Error> {
+            self.engine.request_rematch(info).await
+        }
+
+        async fn display_victory(
+            &mut self,
+            info: client::ui::ClientInfo<'_>,
+        ) -> Result<(), Self::Error> {
+            self.engine.display_victory(info).await
+        }
+
+        async fn display_loss(
+            &mut self,
+            info: client::ui::ClientInfo<'_>,
+        ) -> Result<(), Self::Error> {
+            self.engine.display_loss(info).await
+        }
+    }
+
+    /// Passes the lines on, each `delay` late, and records them
+    #[cfg(feature = "client")]
+    async fn relay_lines(
+        from: tokio::io::DuplexStream,
+        mut to: tokio::io::DuplexStream,
+        delay: std::time::Duration,
+        lines: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    ) -> std::io::Result<()> {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+        let mut from = tokio::io::BufReader::new(from).lines();
+        while let Some(line) = from.next_line().await? {
+            tokio::time::sleep(delay).await;
+            lines.lock().unwrap().push(line.clone());
+            to.write_all(format!("{line}\n").as_bytes()).await?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn engine_resumes_cancelled_requests() {
+        let addr = start_server(None).await;
+        let (engine_input, commands_in) = tokio::io::duplex(64);
+        let (commands_out, bot_input) = tokio::io::duplex(64);
+        let (bot_output, answers_in) = tokio::io::duplex(64);
+        let (answers_out, engine_output) = tokio::io::duplex(64);
+        let commands = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        tokio::spawn(relay_lines(
+            commands_in,
+            commands_out,
+            std::time::Duration::ZERO,
+            commands.clone(),
+        ));
+        tokio::spawn(relay_lines(
+            answers_in,
+            answers_out,
+            std::time::Duration::from_millis(5),
+            std::sync::Arc::default(),
+        ));
+        let bot = tokio::spawn(async move {
+            let mut strategy = Hunt::new(rand::rngs::StdRng::seed_from_u64(1));
+            engine::serve(
+                &mut strategy,
+                tokio::io::BufReader::new(bot_input),
+                bot_output,
+            )
+            .await
+        });
+
+        let engine = Engine::connect(
+            engine_input,
+            engine_output,
+            std::time::Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        let mut impatient = Impatient {
+            engine,
+            cancelled: 0,
+        };
+        let mut random = Bot::new(by_name("random", 2).unwrap());
+        let (hunt, random) = tokio::join!(
+            play(&mut impatient, addr, "engine"),
+            play(&mut random, addr, "random"),
+        );
+        assert!(hunt.unwrap());
+        assert!(!random.unwrap());
+        assert!(impatient.cancelled > 0);
+
+        // every line the bot got is recorded, once it stopped
+        drop(impatient);
+        bot.await.unwrap().unwrap();
+        let commands = commands.lock().unwrap();
+        // every target is asked for once, and every result told once
+        let shots = commands
+            .iter()
+            .filter(|command| *command == "target" || command.starts_with("result "));
+        for (i, command) in shots.enumerate() {
+            assert_eq!(i % 2 == 0, command == "target", "{command}");
+        }
+        let outcomes = commands.iter().filter(|command| *command == "victory");
+        assert_eq!(outcomes.count(), 1);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn computer_plays_waiting_player() {
+        let addr = start_server(Some(server::computer::Config {
+            wait: std::time::Duration::ZERO,
+            strategy: "random".to_string(),
+        }))
+        .await;
+        let mut hunt = Bot::new(by_name("hunt", 1).unwrap());
+        assert!(play(&mut hunt, addr, "hunt").await.unwrap());
+
+        // nobody may pose as the computer
+        assert_eq!(
+            play(&mut hunt, addr, server::computer::NAME).await,
+            Err("client :: handshake rejected :: invalid name".to_string())
+        );
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn engine_times_out() {
+        use tokio::io::AsyncWriteExt;
+
+        let timeout = std::time::Duration::from_millis(50);
+        let (engine_input, _bot_input) = tokio::io::duplex(64);
+        let (_bot_output, engine_output) = tokio::io::duplex(64);
+        assert!(matches!(
+            Engine::connect(engine_input, engine_output, timeout).await,
+            Err(engine::Error::Timeout("ziel 1", _))
+        ));
+
+        // a bot too slow once is given up on, its late answers are never read
+        let (engine_input, _bot_input) = tokio::io::duplex(64);
+        let (mut bot_output, engine_output) = tokio::io::duplex(64);
+        bot_output.write_all(b"ready\n").await.unwrap();
+        let mut engine = Engine::connect(engine_input, engine_output, timeout)
+            .await
+            .unwrap();
+        assert!(matches!(
+            client::UI::request_ships(&mut engine).await,
+            Err(engine::Error::Timeout("place", _))
+        ));
+        bot_output.write_all(b"B2-B6\n").await.unwrap();
+        assert!(matches!(
+            client::UI::request_ships(&mut engine).await,
+            Err(engine::Error::Exited)
+        ));
+    }
+}
@@ -0,0 +1,30 @@
+use rand::{rngs::StdRng, Rng};
+
+/// Shoots at random positions, that were not shot at yet
+pub struct Random {
+    rng: StdRng,
+    untried: Vec<logic::Position>,
+}
+
+impl Random {
+    pub fn new(rng: StdRng) -> Random {
+        Random {
+            rng,
+            untried: crate::positions().collect(),
+        }
+    }
+}
+
+impl crate::Strategy for Random {
+    fn place_ships(&mut self) -> logic::Ships {
+        self.untried = crate::positions().collect();
+        crate::random_ships(&mut self.rng)
+    }
+
+    fn choose_target(&mut self) -> logic::Position {
+        let i = self.rng.random_range(0..self.untried.len());
+        self.untried.swap_remove(i)
+    }
+
+    fn observe(&mut self, _: logic::Position, _: logic::board::AttackInfo) {}
+}
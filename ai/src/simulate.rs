@@ -0,0 +1,144 @@
+use rand::{Rng, SeedableRng};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("ai :: simulate :: unknown strategy :: {0}")]
+    UnknownStrategy(String),
+    #[error("ai :: simulate :: {0} :: {1}")]
+    InvalidTarget(String, logic::board::AlreadyHitError),
+}
+
+/// z-score of a two-sided 95% confidence interval
+const Z_95: f64 = 1.96;
+
+/// How one strategy did over all simulated matches
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerReport {
+    pub name: String,
+    pub games: u32,
+    pub wins: u32,
+    /// Shots fired in every won match, ascending
+    pub shots_to_win: Vec<u16>,
+}
+
+impl PlayerReport {
+    pub fn win_rate(&self) -> f64 {
+        self.wins as f64 / self.games as f64
+    }
+
+    /// 95% Wilson score interval of the win rate
+    pub fn win_rate_interval(&self) -> (f64, f64) {
+        let (n, p) = (self.games as f64, self.win_rate());
+        let z2 = Z_95 * Z_95;
+        let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let spread = Z_95 / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+        ((center - spread).max(0.0), (center + spread).min(1.0))
+    }
+
+    pub fn mean_shots_to_win(&self) -> Option<f64> {
+        (!self.shots_to_win.is_empty()).then(|| {
+            self.shots_to_win.iter().map(|&s| s as f64).sum::<f64>()
+                / self.shots_to_win.len() as f64
+        })
+    }
+
+    /// 95% confidence interval of the mean shots to win, `None` with fewer
+    /// than two wins
+    pub fn mean_shots_to_win_interval(&self) -> Option<(f64, f64)> {
+        let n = self.shots_to_win.len() as f64;
+        let mean = self.mean_shots_to_win().filter(|_| n >= 2.0)?;
+        let variance = self
+            .shots_to_win
+            .iter()
+            .map(|&s| (s as f64 - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0);
+        let spread = Z_95 * (variance / n).sqrt();
+        Some((mean - spread, mean + spread))
+    }
+
+    /// Shots to win, which `percent` of the wins needed at most
+    pub fn percentile(&self, percent: f64) -> Option<u16> {
+        let rank = (percent / 100.0 * self.shots_to_win.len() as f64).ceil() as usize;
+        self.shots_to_win
+            .get(rank.clamp(1, self.shots_to_win.len().max(1)) - 1)
+            .copied()
+    }
+}
+
+/// Results of a simulation, reproducible with the same seed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub seed: u64,
+    pub games: u32,
+    pub players: [PlayerReport; 2],
+}
+
+/// Plays one match, `first` shooting first. A hit lets the shooter go again,
+/// as on the server. Returns the index of the winner and the shots of both.
+pub fn play(
+    strategies: [&mut dyn crate::Strategy; 2],
+    first: usize,
+) -> Result<(usize, [u16; 2]), (usize, logic::board::AlreadyHitError)> {
+    let players = [logic::game::Player::One, logic::game::Player::Two];
+    let mut game = logic::Game::new(players[first]);
+    for player in players {
+        game.place(player, strategies[player.index()].place_ships())
+            .expect("fleets are placed once, before the first shot");
+    }
+
+    let mut shots = [0, 0];
+    loop {
+        let logic::game::Phase::Turn(shooter) = game.phase() else {
+            unreachable!("the match is played until it is over");
+        };
+        let index = shooter.index();
+        let target = strategies[index].choose_target();
+        let events = game.fire(shooter, target).map_err(|err| match err {
+            logic::game::CommandError::AlreadyHit(err) => (index, err),
+            err => unreachable!("only the current player shoots :: {err}"),
+        })?;
+        shots[index] += 1;
+
+        for event in events {
+            match event {
+                logic::game::Event::Victory { winner, .. } => return Ok((winner.index(), shots)),
+                event => strategies[index]
+                    .observe(target, event.attack_info().expect("shots have a result")),
+            }
+        }
+    }
+}
+
+/// Plays `games` matches between two built-in strategies, taking turns at
+/// shooting first
+pub fn run(names: [&str; 2], games: u32, seed: u64) -> Result<Report, Error> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let [strategy1, strategy2] = names.map(|name| {
+        crate::by_name(name, rng.random()).ok_or_else(|| Error::UnknownStrategy(name.to_string()))
+    });
+    let mut strategies = [strategy1?, strategy2?];
+
+    let mut players = names.map(|name| PlayerReport {
+        name: name.to_string(),
+        games,
+        wins: 0,
+        shots_to_win: vec![],
+    });
+    for game in 0..games {
+        let [strategy1, strategy2] = &mut strategies;
+        let (winner, shots) = play([&mut **strategy1, &mut **strategy2], game as usize % 2)
+            .map_err(|(player, err)| Error::InvalidTarget(names[player].to_string(), err))?;
+        players[winner].wins += 1;
+        players[winner].shots_to_win.push(shots[winner]);
+    }
+
+    for player in &mut players {
+        player.shots_to_win.sort_unstable();
+    }
+    Ok(Report {
+        seed,
+        games,
+        players,
+    })
+}
@@ -1,13 +1,18 @@
-use protocol::{client, server};
-use tokio::{
-    io::{self, AsyncWriteExt},
-    net, time,
-};
-
-use arrayvec::ArrayVec;
+use protocol::{client, server, session::Incoming};
+use tokio::{io, net, sync::mpsc, time};
 
 pub mod ui;
-pub use ui::UI;
+pub use protocol::client::Identity;
+pub use ui::{ClientEvent, Snapshot, UI};
+
+/// How the client connects to the server
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The name the player is known by on the server
+    pub identity: Identity,
+    /// Pings the server, so a dead connection ends the game
+    pub heartbeat: protocol::session::Heartbeat,
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error<I: UI> {
@@ -21,6 +26,8 @@ pub enum Error<I: UI> {
     UnexpectedRequest(server::Message),
     #[error("client :: server request :: unexpected termination")]
     UnexpectedTerminationRequest,
+    #[error("client :: handshake rejected :: {0}")]
+    HandshakeRejected(server::Rejection),
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -30,156 +37,347 @@ pub enum AttackInfo {
 }
 
 pub struct Client {
-    stream: net::TcpStream,
+    session: protocol::session::Sender<client::Message, server::Message>,
+    incoming: protocol::session::Receiver<server::Message>,
+
+    snapshot: Snapshot,
+    /// Receivers of every event, see [`Client::subscribe`]
+    subscribers: Vec<mpsc::UnboundedSender<ClientEvent>>,
+    /// Ticks whenever [`ClientEvent::Health`] is due
+    health: time::Interval,
+    /// When the running match has started
+    started: time::Instant,
+    /// A request of the server that arrived while another one was answered
+    pending: Option<Incoming<server::Message>>,
+    /// The UI answers a request of the server, rendering every change itself
+    requesting: bool,
 
-    messages: Vec<ui::Message>,
+    rematch_declined: bool,
+    surrendered: bool,
+}
+
+/// The events of a client, as returned by [`Client::subscribe`]
+#[derive(Debug)]
+pub struct Events(mpsc::UnboundedReceiver<ClientEvent>);
 
-    ships: logic::Ships,
-    client_hit_map: [[Option<AttackInfo>; 10]; 10],
-    opponent_hit_map: [[Option<AttackInfo>; 10]; 10],
-    opponent_ships: ArrayVec<logic::ship::Ship, 5>,
+impl Events {
+    /// The next event, `None` once the client is gone
+    pub async fn next(&mut self) -> Option<ClientEvent> {
+        self.0.recv().await
+    }
 }
 
 impl Client {
     pub async fn handshake<I: UI>(
         ui: &mut I,
         addr: std::net::SocketAddr,
+        config: &Config,
     ) -> Result<Client, Error<I>> {
-        let ships = ui.request_ships().map_err(ui::Error::to_ui_error)?;
+        let ships = ui.request_ships().await.map_err(ui::Error::to_ui_error)?;
 
-        let mut stream = net::TcpStream::connect(addr).await?;
-        protocol::write(&mut stream, client::Message::HandShake).await?;
-        match protocol::read(&mut stream).await? {
+        let (session, incoming) =
+            protocol::session::start(net::TcpStream::connect(addr).await?, config.heartbeat);
+        match session
+            .request(client::Message::HandShake(config.identity.clone()))
+            .await?
+        {
             server::Message::Handshake => {}
+            server::Message::RejectHandshake(rejection) => {
+                return Err(Error::HandshakeRejected(rejection))
+            }
             req => return Err(Error::UnexpectedRequest(req)),
         }
 
+        let mut health = time::interval(time::Duration::from_secs(1));
+        health.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
         Ok(Client {
-            stream,
-            ships,
-            messages: vec![],
-            opponent_hit_map: [[None; 10]; 10],
-            client_hit_map: [[None; 10]; 10],
-            opponent_ships: ArrayVec::new(),
+            session,
+            incoming,
+            snapshot: Snapshot::new(config.identity.name.clone(), ships),
+            subscribers: vec![],
+            health,
+            started: time::Instant::now(),
+            pending: None,
+            requesting: false,
+            rematch_declined: false,
+            surrendered: false,
         })
     }
 
-    async fn handle_request<I: UI>(&mut self, ui: &mut I) -> Result<Option<bool>, Error<I>> {
-        let mut state = None;
-        let info = unsafe { &*(self as *const Self) }.into();
-        let request = tokio::select! {
-            err = async {
-                loop {
-                    match ui.display_board(info) {
-                        Ok(()) => time::sleep(time::Duration::from_millis(50)).await,
-                        Err(err) => break ui::Error::to_ui_error(err).into(),
+    /// The current state, which every [`ClientEvent`] changes
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    /// Receives every [`ClientEvent`] from now on, while the client plays
+    pub fn subscribe(&mut self) -> Events {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.subscribers.push(sender);
+        Events(receiver)
+    }
+
+    fn info(&self) -> ui::ClientInfo<'_> {
+        ui::ClientInfo::new(&self.snapshot, &self.session)
+    }
+
+    /// Applies the event to the state, and passes it on to the subscribers
+    /// and the UI
+    fn emit<I: UI>(&mut self, ui: &mut I, event: ClientEvent) -> Result<(), Error<I>> {
+        self.snapshot.apply(&event);
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        if !self.requesting {
+            ui.display_board(self.info(), &event)
+                .map_err(ui::Error::to_ui_error)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the answer of the UI, while the messages of the server keep
+    /// being handled. Every event cancels the request, which is made again
+    /// with the new state. `None` once the server sent a request, which is
+    /// left in [`Client::pending`].
+    async fn ask<I: UI, T>(
+        &mut self,
+        ui: &mut I,
+        mut request: impl AsyncFnMut(&mut I, ui::ClientInfo<'_>) -> Result<T, I::Error>,
+    ) -> Result<Option<T>, Error<I>> {
+        loop {
+            let info = ui::ClientInfo::new(&self.snapshot, &self.session);
+            tokio::select! {
+                answer = request(ui, info) => {
+                    return Ok(Some(answer.map_err(ui::Error::to_ui_error)?))
+                }
+                incoming = self.incoming.recv() => match incoming? {
+                    Incoming::Message(server::Message::InformChat(text)) => {
+                        self.emit(ui, ClientEvent::OpponentChat(text))?
+                    }
+                    Incoming::Message(server::Message::InformQueue(status)) => {
+                        self.emit(ui, ClientEvent::Queue(status))?
+                    }
+                    Incoming::Message(message) => return Err(Error::UnexpectedRequest(message)),
+                    request => {
+                        self.pending = Some(request);
+                        return Ok(None);
                     }
+                },
+                _ = self.health.tick() => {
+                    self.emit(ui, ClientEvent::Health(self.session.health()))?
                 }
-            } => Err(err),
-            req = protocol::read(&mut self.stream) => req.map_err(Error::<I>::from),
-        }?;
-        let response = match request {
-            server::Message::RequestShips => client::Message::ReturnShips(self.ships.clone()),
-            server::Message::RequestTarget => {
-                let target = ui
-                    .request_target((self as &Client).into())
-                    .map_err(ui::Error::to_ui_error)?;
-                if self.opponent_hit_map[target].is_some() {
-                    return Err(Error::UIError(ui::Error::InvalidTarget));
+            }
+        }
+    }
+
+    /// Lets the UI answer a request of the server. `None` once the server
+    /// sent the next request instead, abandoning this one.
+    async fn answer<I: UI, T>(
+        &mut self,
+        ui: &mut I,
+        request: impl AsyncFnMut(&mut I, ui::ClientInfo<'_>) -> Result<T, I::Error>,
+    ) -> Result<Option<T>, Error<I>> {
+        self.requesting = true;
+        let answer = self.ask(ui, request).await;
+        self.requesting = false;
+        answer
+    }
+
+    /// Sends a command issued by the player right away
+    async fn handle_command<I: UI>(
+        &mut self,
+        ui: &mut I,
+        command: ui::Command,
+    ) -> Result<(), Error<I>> {
+        match command {
+            ui::Command::Surrender if !self.surrendered => {
+                self.surrendered = true;
+                self.session.send(client::Message::Surrender)?;
+            }
+            ui::Command::Surrender => {}
+            ui::Command::Chat(mut text) => {
+                protocol::truncate_chat(&mut text);
+                self.session.send(client::Message::Chat(text.clone()))?;
+                self.emit(ui, ClientEvent::ClientChat(text))?;
+            }
+            ui::Command::Leaderboard => {
+                match self
+                    .session
+                    .request(client::Message::RequestLeaderboard)
+                    .await?
+                {
+                    server::Message::ReturnLeaderboard(standings) => {
+                        self.emit(ui, ClientEvent::Leaderboard(standings))?
+                    }
+                    req => return Err(Error::UnexpectedRequest(req)),
                 }
-                client::Message::ReturnTarget(target)
             }
-            server::Message::InformTargetSelection => {
-                self.messages.push(ui::Message::OpponentSelectsTarget);
-                client::Message::Acknowledge
+        }
+        Ok(())
+    }
+
+    async fn handle_request<I: UI>(&mut self, ui: &mut I) -> Result<Option<bool>, Error<I>> {
+        let mut state = None;
+        let incoming = loop {
+            if let Some(incoming) = self.pending.take() {
+                break incoming;
             }
-            server::Message::InformTargetMissClient(pos) => {
-                self.client_hit_map[pos] = Some(AttackInfo::Miss);
-                self.messages.push(ui::Message::OpponentMissedClient(pos));
-                client::Message::Acknowledge
+            if let Some(command) = self
+                .ask(ui, async |ui: &mut I, info| ui.next_command(info).await)
+                .await?
+            {
+                self.handle_command(ui, command).await?;
             }
-            server::Message::InformTargetMissOpponent(pos) => {
-                self.opponent_hit_map[pos] = Some(AttackInfo::Miss);
-                self.messages.push(ui::Message::ClientMissedOpponent(pos));
-                client::Message::Acknowledge
+        };
+        let (id, request) = match incoming {
+            Incoming::Request(id, request) => (id, request),
+            Incoming::Message(message) => return Err(Error::UnexpectedRequest(message)),
+        };
+
+        let (response, event) = match request {
+            server::Message::RequestShips => {
+                // the first match uses the ships placed before the handshake
+                let ships = match self.snapshot.outcome {
+                    Some(_) => ui.request_ships().await.map_err(ui::Error::to_ui_error)?,
+                    None => self.snapshot.ships,
+                };
+                self.started = time::Instant::now();
+                (
+                    client::Message::ReturnShips(ships),
+                    Some(ClientEvent::MatchStarted(ships)),
+                )
             }
-            server::Message::InformTargetHitClient(pos) => {
-                self.client_hit_map[pos] = Some(AttackInfo::Hit);
-                self.messages.push(ui::Message::OpponentHitClient(pos));
-                client::Message::Acknowledge
+            server::Message::RequestTarget => {
+                let target = loop {
+                    // the server abandons the request once it received the surrender
+                    if self.surrendered {
+                        return Ok(None);
+                    }
+                    let answer = self
+                        .answer(ui, async |ui: &mut I, info| ui.request_target(info).await)
+                        .await?;
+                    match answer {
+                        Some(ui::Target::Position(target)) => break target,
+                        Some(ui::Target::Command(command)) => {
+                            self.handle_command(ui, command).await?
+                        }
+                        None => return Ok(None),
+                    }
+                };
+                if self.snapshot.opponent_hit_map[target].is_some() {
+                    return Err(Error::UIError(ui::Error::InvalidTarget));
+                }
+                (client::Message::ReturnTarget(target), None)
             }
-            server::Message::InformTargetHitOpponent(pos) => {
-                self.opponent_hit_map[pos] = Some(AttackInfo::Hit);
-                self.messages.push(ui::Message::ClientHitOpponent(pos));
-                client::Message::Acknowledge
+            server::Message::InformTargetSelection => (
+                client::Message::Acknowledge,
+                Some(ClientEvent::OpponentSelectsTarget),
+            ),
+            server::Message::InformTargetMissClient(pos) => (
+                client::Message::Acknowledge,
+                Some(ClientEvent::ShotAtClient(
+                    pos,
+                    AttackInfo::Miss,
+                    self.started.elapsed(),
+                )),
+            ),
+            server::Message::InformTargetMissOpponent(pos) => (
+                client::Message::Acknowledge,
+                Some(ClientEvent::ShotAtOpponent(
+                    pos,
+                    AttackInfo::Miss,
+                    self.started.elapsed(),
+                )),
+            ),
+            server::Message::InformTargetHitClient(pos) => (
+                client::Message::Acknowledge,
+                Some(ClientEvent::ShotAtClient(
+                    pos,
+                    AttackInfo::Hit,
+                    self.started.elapsed(),
+                )),
+            ),
+            server::Message::InformTargetHitOpponent(pos) => (
+                client::Message::Acknowledge,
+                Some(ClientEvent::ShotAtOpponent(
+                    pos,
+                    AttackInfo::Hit,
+                    self.started.elapsed(),
+                )),
+            ),
+            server::Message::RequestRematch => {
+                let Some(accept) = self
+                    .answer(ui, async |ui: &mut I, info| ui.request_rematch(info).await)
+                    .await?
+                else {
+                    return Ok(None);
+                };
+                self.rematch_declined = !accept;
+                (client::Message::ReturnRematch(accept), None)
             }
             server::Message::InformLoss => {
-                state = Some(false);
-                client::Message::Acknowledge
+                self.surrendered = false;
+                (
+                    client::Message::Acknowledge,
+                    Some(ClientEvent::Outcome(false)),
+                )
             }
             server::Message::InformVictory => {
-                state = Some(true);
-                client::Message::Acknowledge
+                self.surrendered = false;
+                (
+                    client::Message::Acknowledge,
+                    Some(ClientEvent::Outcome(true)),
+                )
             }
+            server::Message::InformScore(score) => (
+                client::Message::Acknowledge,
+                Some(ClientEvent::Score(score)),
+            ),
             server::Message::Invalid => {
                 return Err(Error::UnexpectedRequest(server::Message::Invalid))
             }
-            server::Message::TerminateConnection => {
-                return Err(Error::UnexpectedTerminationRequest)
-            }
-            server::Message::InformShipSunkenClient(ship) => {
-                let length = match ship.to_ship_plan() {
-                    logic::ship::ShipPlan::Horizontal { length, .. } => length,
-                    logic::ship::ShipPlan::Vertical { length, .. } => length,
-                };
-                self.messages.push(ui::Message::ClientShipSunk(length));
-                client::Message::Acknowledge
-            }
-            server::Message::InformShipSunkenOpponent(ship) => {
-                let length = match ship.to_ship_plan() {
-                    logic::ship::ShipPlan::Horizontal { length, .. } => length,
-                    logic::ship::ShipPlan::Vertical { length, .. } => length,
-                };
-                self.messages.push(ui::Message::OpponentShipSunk(length));
-                self.opponent_ships.push(ship);
-                client::Message::Acknowledge
-            }
+            server::Message::TerminateConnection => match self.snapshot.outcome {
+                Some(victory) => {
+                    state = Some(victory);
+                    (client::Message::Acknowledge, None)
+                }
+                None => return Err(Error::UnexpectedTerminationRequest),
+            },
+            server::Message::InformShipSunkenClient(ship) => (
+                client::Message::Acknowledge,
+                Some(ClientEvent::ClientShipSunk(ship)),
+            ),
+            server::Message::InformShipSunkenOpponent(ship) => (
+                client::Message::Acknowledge,
+                Some(ClientEvent::OpponentShipSunk(ship)),
+            ),
             req => return Err(Error::UnexpectedRequest(req)),
         };
 
-        let info = unsafe { &*(self as *const Self) }.into();
-        tokio::select! {
-            err = async {
-                loop {
-                    match ui.display_board(info) {
-                        Ok(()) => time::sleep(time::Duration::from_millis(50)).await,
-                        Err(err) => break ui::Error::to_ui_error(err).into(),
-                    }
-                }
-            } => Err(err),
-            req = protocol::write(&mut self.stream, response) => req.map_err(Error::<I>::from),
-        }?;
+        if let Some(event) = event {
+            self.emit(ui, event)?;
+        }
+        self.session.respond(id, response)?;
 
         Ok(state)
     }
 
+    /// Plays matches until the server terminates the connection, returning
+    /// whether the last match was won.
     pub async fn play<I: UI>(mut self, ui: &mut I) -> Result<bool, Error<I>> {
         loop {
             match self.handle_request(ui).await {
                 Ok(Some(victory)) => {
-                    if let Ok(server::Message::TerminateConnection) =
-                        protocol::read(&mut self.stream).await
-                    {
-                        let _ =
-                            protocol::write(&mut self.stream, client::Message::Acknowledge).await;
-                    };
-                    self.stream.shutdown().await?;
-                    if victory {
-                        ui.display_victory((&self).into())
-                            .map_err(ui::Error::to_ui_error)?;
-                    } else {
-                        ui.display_loss((&self).into())
-                            .map_err(ui::Error::to_ui_error)?;
+                    self.session.close().await?;
+                    // a player declining the rematch has already seen the result
+                    if !self.rematch_declined {
+                        if victory {
+                            ui.display_victory(self.info())
+                                .await
+                                .map_err(ui::Error::to_ui_error)?;
+                        } else {
+                            ui.display_loss(self.info())
+                                .await
+                                .map_err(ui::Error::to_ui_error)?;
+                        }
                     }
                     return Ok(victory);
                 }
@@ -190,6 +388,11 @@ impl Client {
     }
 }
 
-pub async fn play_round<I: UI>(ui: &mut I, addr: std::net::SocketAddr) -> Result<bool, Error<I>> {
-    Client::handshake(ui, addr).await?.play(ui).await
+/// Plays on the server at `addr`
+pub async fn play_round<I: UI>(
+    ui: &mut I,
+    addr: std::net::SocketAddr,
+    config: &Config,
+) -> Result<bool, Error<I>> {
+    Client::handshake(ui, addr, config).await?.play(ui).await
 }
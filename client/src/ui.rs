@@ -1,3 +1,9 @@
+use std::{future::Future, time::Duration};
+
+pub use protocol::server::{QueueStatus, Score, Standing};
+pub use protocol::session::Health;
+pub use protocol::MAX_CHAT_LEN;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error<I: UI> {
     #[error("ui :: {0}")]
@@ -15,31 +21,325 @@ impl<I: UI> Error<I> {
 /// Info about the client and game status
 #[derive(Clone, Copy, Debug)]
 pub struct ClientInfo<'i> {
+    /// The name the player is known by on the server.
+    pub name: &'i str,
     /// Messages received from the server will be collected here. The messages
     /// get pushed to a vector, so the last one is the newest.
     pub messages: &'i [Message],
     /// The player's ships.
     pub ships: &'i logic::Ships,
+    /// Damage of the player's ships, in the order of [`ClientInfo::ships`].
+    pub fleet: &'i [ShipStatus; 5],
     /// It specifies where the client is already hit. The array is indexed by `[y][x]`
     pub client_hit_map: &'i [[Option<crate::AttackInfo>; 10]; 10],
     /// It specifies where the opponent is already hit. The array is indexed by `[y][x]`
     pub opponent_hit_map: &'i [[Option<crate::AttackInfo>; 10]; 10],
     /// Opponent ships that sunk will be collected in this slice.
     pub opponent_ships: &'i [logic::ship::Ship],
+    /// Every shot of the running match, the last one is the newest.
+    pub history: &'i [Turn],
+    /// Result of the finished match, `true` on victory. It is `None` as long
+    /// as the match is running.
+    pub outcome: Option<bool>,
+    /// Score of the series, available after the first match has finished.
+    pub score: Option<Score>,
+    /// Place in the matchmaking queue, while waiting for an opponent.
+    pub queue: Option<QueueStatus>,
+    /// Latency and last sign of life of the server.
+    pub connection: Connection<'i>,
+    /// The best rated players, once requested with [`Command::Leaderboard`].
+    pub leaderboard: Option<&'i [Standing]>,
 }
 
-impl<'i> From<&'i crate::Client> for ClientInfo<'i> {
-    fn from(client: &'i crate::Client) -> Self {
+impl<'i> ClientInfo<'i> {
+    pub(crate) fn new(
+        snapshot: &'i Snapshot,
+        session: &'i protocol::session::Sender<
+            protocol::client::Message,
+            protocol::server::Message,
+        >,
+    ) -> ClientInfo<'i> {
         ClientInfo {
-            messages: &client.messages,
-            ships: &client.ships,
-            client_hit_map: &client.client_hit_map,
-            opponent_hit_map: &client.opponent_hit_map,
-            opponent_ships: &client.opponent_ships,
+            name: &snapshot.name,
+            messages: &snapshot.messages,
+            ships: &snapshot.ships,
+            fleet: &snapshot.fleet,
+            client_hit_map: &snapshot.client_hit_map,
+            opponent_hit_map: &snapshot.opponent_hit_map,
+            opponent_ships: &snapshot.opponent_ships,
+            history: &snapshot.history,
+            outcome: snapshot.outcome,
+            score: snapshot.score,
+            queue: snapshot.queue,
+            connection: Connection(session),
+            leaderboard: snapshot.leaderboard.as_deref(),
         }
     }
 }
 
+impl ClientInfo<'_> {
+    /// Every ship of the opponent's fleet by length, longest first, and
+    /// whether it has sunk
+    pub fn opponent_fleet(&self) -> [(u8, bool); 5] {
+        let mut sunk: Vec<_> = self.opponent_ships.iter().map(ship_length).collect();
+        logic::ship::SHIP_LENGTHS.map(
+            |length| match sunk.iter().position(|&sunk| sunk == length) {
+                Some(index) => {
+                    sunk.swap_remove(index);
+                    (length, true)
+                }
+                None => (length, false),
+            },
+        )
+    }
+
+    /// Lengths of the opponent's ships still afloat, longest first
+    pub fn remaining_opponent_ships(&self) -> Vec<u8> {
+        self.opponent_fleet()
+            .into_iter()
+            .filter(|&(_, sunk)| !sunk)
+            .map(|(length, _)| length)
+            .collect()
+    }
+}
+
+/// Live view on the connection to the server, which stays up to date while
+/// the [`UI`] waits for the player
+#[derive(Clone, Copy)]
+pub struct Connection<'i>(
+    &'i protocol::session::Sender<protocol::client::Message, protocol::server::Message>,
+);
+
+impl Connection<'_> {
+    pub fn health(&self) -> Health {
+        self.0.health()
+    }
+}
+
+impl std::fmt::Debug for Connection<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Connection").field(&self.health()).finish()
+    }
+}
+
+/// A change of the client state. Applying every event to a [`Snapshot`], in
+/// the order they happened, leads to the state the client has.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClientEvent {
+    /// Place in the matchmaking queue, while waiting for an opponent
+    Queue(QueueStatus),
+    /// A new match begins, with the fleet the player placed
+    MatchStarted(logic::Ships),
+    OpponentSelectsTarget,
+    /// The opponent shot at the player, after the given time of the match
+    ShotAtClient(logic::Position, crate::AttackInfo, Duration),
+    /// The player shot at the opponent, after the given time of the match
+    ShotAtOpponent(logic::Position, crate::AttackInfo, Duration),
+    ClientShipSunk(logic::ship::Ship),
+    OpponentShipSunk(logic::ship::Ship),
+    ClientChat(String),
+    OpponentChat(String),
+    /// The match is over, `true` on victory
+    Outcome(bool),
+    Score(Score),
+    Leaderboard(Vec<Standing>),
+    /// Sent every second, as [`ClientInfo::connection`] changes all the time.
+    /// It interrupts a request like any other event, so it gets rendered.
+    Health(Health),
+}
+
+/// The state of a client, owned and independent of the connection
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub name: String,
+    pub messages: Vec<Message>,
+    pub ships: logic::Ships,
+    /// See [`ClientInfo::fleet`]
+    pub fleet: [ShipStatus; 5],
+    /// Indexed by `[y][x]`, see [`ClientInfo::client_hit_map`]
+    pub client_hit_map: [[Option<crate::AttackInfo>; 10]; 10],
+    /// Indexed by `[y][x]`, see [`ClientInfo::opponent_hit_map`]
+    pub opponent_hit_map: [[Option<crate::AttackInfo>; 10]; 10],
+    pub opponent_ships: Vec<logic::ship::Ship>,
+    /// Every shot of the running match, see [`ClientInfo::history`]
+    pub history: Vec<Turn>,
+    pub outcome: Option<bool>,
+    pub score: Option<Score>,
+    pub queue: Option<QueueStatus>,
+    pub leaderboard: Option<Vec<Standing>>,
+}
+
+impl Snapshot {
+    /// The state right after the handshake, before any event
+    pub fn new(name: String, ships: logic::Ships) -> Snapshot {
+        Snapshot {
+            name,
+            messages: vec![],
+            ships,
+            fleet: ships.into_ship_array().map(ShipStatus::new),
+            client_hit_map: [[None; 10]; 10],
+            opponent_hit_map: [[None; 10]; 10],
+            opponent_ships: vec![],
+            history: vec![],
+            outcome: None,
+            score: None,
+            queue: None,
+            leaderboard: None,
+        }
+    }
+
+    pub fn apply(&mut self, event: &ClientEvent) {
+        match event {
+            ClientEvent::Queue(status) => self.queue = Some(*status),
+            ClientEvent::MatchStarted(ships) => {
+                *self = Snapshot {
+                    name: std::mem::take(&mut self.name),
+                    score: self.score,
+                    leaderboard: self.leaderboard.take(),
+                    ..Snapshot::new(String::new(), *ships)
+                };
+            }
+            ClientEvent::OpponentSelectsTarget => {
+                self.messages.push(Message::OpponentSelectsTarget)
+            }
+            ClientEvent::ShotAtClient(pos, attack_info, elapsed) => {
+                self.record(Side::Opponent, *pos, *attack_info, *elapsed);
+                if let crate::AttackInfo::Hit = attack_info {
+                    let hit = self
+                        .fleet
+                        .iter_mut()
+                        .find(|status| status.ship.into_iter().any(|part| part == *pos));
+                    if let Some(status) = hit {
+                        status.hits += 1;
+                    }
+                }
+                self.client_hit_map[*pos] = Some(*attack_info);
+                self.messages.push(match attack_info {
+                    crate::AttackInfo::Hit => Message::OpponentHitClient(*pos),
+                    crate::AttackInfo::Miss => Message::OpponentMissedClient(*pos),
+                });
+            }
+            ClientEvent::ShotAtOpponent(pos, attack_info, elapsed) => {
+                self.record(Side::Client, *pos, *attack_info, *elapsed);
+                self.opponent_hit_map[*pos] = Some(*attack_info);
+                self.messages.push(match attack_info {
+                    crate::AttackInfo::Hit => Message::ClientHitOpponent(*pos),
+                    crate::AttackInfo::Miss => Message::ClientMissedOpponent(*pos),
+                });
+            }
+            ClientEvent::ClientShipSunk(ship) => {
+                self.mark_sunk(Side::Opponent, *ship);
+                if let Some(status) = self.fleet.iter_mut().find(|status| status.ship == *ship) {
+                    status.sunk = true;
+                }
+                self.messages
+                    .push(Message::ClientShipSunk(ship_length(ship)))
+            }
+            ClientEvent::OpponentShipSunk(ship) => {
+                self.mark_sunk(Side::Client, *ship);
+                self.messages
+                    .push(Message::OpponentShipSunk(ship_length(ship)));
+                self.opponent_ships.push(*ship);
+            }
+            ClientEvent::ClientChat(text) => self.messages.push(Message::ClientChat(text.clone())),
+            ClientEvent::OpponentChat(text) => {
+                self.messages.push(Message::OpponentChat(text.clone()))
+            }
+            ClientEvent::Outcome(victory) => self.outcome = Some(*victory),
+            ClientEvent::Score(score) => self.score = Some(*score),
+            ClientEvent::Leaderboard(standings) => self.leaderboard = Some(standings.clone()),
+            ClientEvent::Health(_) => {}
+        }
+    }
+
+    fn record(
+        &mut self,
+        shooter: Side,
+        target: logic::Position,
+        result: crate::AttackInfo,
+        elapsed: Duration,
+    ) {
+        let number = match self.history.last() {
+            Some(turn) if turn.shooter == shooter => turn.number,
+            Some(turn) => turn.number + 1,
+            None => 1,
+        };
+        self.history.push(Turn {
+            number,
+            shooter,
+            target,
+            result,
+            sunk: None,
+            elapsed,
+        });
+    }
+
+    /// Links the ship to the shot that sunk it, which is told right before
+    fn mark_sunk(&mut self, shooter: Side, ship: logic::ship::Ship) {
+        if let Some(turn) = self
+            .history
+            .iter_mut()
+            .rev()
+            .find(|turn| turn.shooter == shooter)
+        {
+            turn.sunk = Some(ship);
+        }
+    }
+}
+
+/// Damage of one of the player's ships
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShipStatus {
+    pub ship: logic::ship::Ship,
+    /// Parts of the ship the opponent has hit
+    pub hits: u8,
+    /// Set once the server told the ship has sunk
+    pub sunk: bool,
+}
+
+impl ShipStatus {
+    fn new(ship: logic::ship::Ship) -> ShipStatus {
+        ShipStatus {
+            ship,
+            hits: 0,
+            sunk: false,
+        }
+    }
+
+    pub fn length(&self) -> u8 {
+        ship_length(&self.ship)
+    }
+}
+
+/// Who fired a shot of the [`Snapshot::history`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    Client,
+    Opponent,
+}
+
+/// A single shot of the running match
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Turn {
+    /// Starts at 1, and counts up whenever the other player shoots. All shots
+    /// of a player in a row, after hitting, share the number.
+    pub number: u16,
+    pub shooter: Side,
+    pub target: logic::Position,
+    pub result: crate::AttackInfo,
+    /// The ship the shot has sunk
+    pub sunk: Option<logic::ship::Ship>,
+    /// Time from the start of the match until the shot
+    pub elapsed: Duration,
+}
+
+fn ship_length(ship: &logic::ship::Ship) -> u8 {
+    match ship.to_ship_plan() {
+        logic::ship::ShipPlan::Horizontal { length, .. } => length,
+        logic::ship::ShipPlan::Vertical { length, .. } => length,
+    }
+}
+
 /// This trait needs to be implemented for a working UI implementation.
 /// Any of the return values will get verified, and will return an error, if
 /// invalid.
@@ -48,6 +348,12 @@ impl<'i> From<&'i crate::Client> for ClientInfo<'i> {
 /// functions of this trait return an error.
 /// ([`display_loss`] & [`display_victory`] are no longer connected)
 ///
+/// The functions waiting for the player are async, so the client keeps
+/// handling the connection in the meantime. [`UI::request_target`],
+/// [`UI::request_rematch`] and [`UI::next_command`] get cancelled whenever the
+/// state changes, and are called again with the new [`ClientInfo`]. Progress
+/// the player has made, like the cursor position, needs to be kept in `self`.
+///
 /// Initialization and termination of the UI happen before the implementation
 /// gets bound, and after it gets freed from the [`Client`].
 pub trait UI {
@@ -55,27 +361,73 @@ pub trait UI {
     /// Panicking is not wanted, instead generate nice and readable errors.
     type Error: std::error::Error;
 
-    /// The player will select where to place the ships. This funtion is
-    /// blocking, and will wait until the player has positioned all their ships.
-    fn request_ships(&mut self) -> Result<logic::Ships, Self::Error>;
-    /// The player will select where to attack. This funtion is blocking, and
-    /// will wait until the player has selected a target.
-    fn request_target(&mut self, info: ClientInfo) -> Result<logic::Position, Self::Error>;
+    /// The player will select where to place the ships, resolving once they
+    /// have positioned all their ships.
+    fn request_ships(&mut self) -> impl Future<Output = Result<logic::Ships, Self::Error>>;
+    /// The player will select where to attack, resolving once they have
+    /// selected a target, or issued a [`Command`] instead.
+    fn request_target(
+        &mut self,
+        info: ClientInfo,
+    ) -> impl Future<Output = Result<Target, Self::Error>>;
+
+    /// The bard will be displayed again, as `event` has changed it. This
+    /// function should only render one frame, it is called for every change
+    /// while no request is outstanding.
+    fn display_board(&mut self, info: ClientInfo, event: &ClientEvent) -> Result<(), Self::Error>;
+    /// The next [`Command`] the player issues, while no request is
+    /// outstanding. It only needs to render, if the input changed what is shown.
+    fn next_command(
+        &mut self,
+        info: ClientInfo,
+    ) -> impl Future<Output = Result<Command, Self::Error>>;
+    /// The player will decide whether to play another match against the same
+    /// opponent. The result of the finished match is found in
+    /// [`ClientInfo::outcome`]. When accepting, [`UI::request_ships`] is
+    /// called again once the opponent has accepted as well. When declining,
+    /// the connection gets closed without calling [`UI::display_victory`] or
+    /// [`UI::display_loss`].
+    fn request_rematch(
+        &mut self,
+        info: ClientInfo,
+    ) -> impl Future<Output = Result<bool, Self::Error>>;
 
-    /// The bard will be displayed. This function should only render one frame,
-    /// before returning, as it is already called every 50ms.
-    fn display_board(&mut self, info: ClientInfo) -> Result<(), Self::Error>;
     /// The bard will be displayed, including a victory screen. This function
-    /// should block until the player terminates the program.
-    fn display_victory(&mut self, info: ClientInfo) -> Result<(), Self::Error>;
+    /// should resolve once the player terminates the program.
+    fn display_victory(
+        &mut self,
+        info: ClientInfo,
+    ) -> impl Future<Output = Result<(), Self::Error>>;
     /// The bard will be displayed, including a loss screen. This function
-    /// should block until the player terminates the program.
-    fn display_loss(&mut self, info: ClientInfo) -> Result<(), Self::Error>;
+    /// should resolve once the player terminates the program.
+    fn display_loss(&mut self, info: ClientInfo) -> impl Future<Output = Result<(), Self::Error>>;
+}
+
+/// Commands the player can issue while a match is running, independent of
+/// what the server has requested.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Concede the match. The surrender is sent right away and ends the
+    /// match, even while a request is outstanding.
+    Surrender,
+    /// Send a message to the opponent. It gets cut to [`MAX_CHAT_LEN`] bytes.
+    /// When returned by [`UI::request_target`], the target is requested again.
+    Chat(String),
+    /// Ask the server for the best rated players, see [`ClientInfo::leaderboard`].
+    /// When returned by [`UI::request_target`], the target is requested again.
+    Leaderboard,
+}
+
+/// The answer of the player to [`UI::request_target`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Position(logic::Position),
+    Command(Command),
 }
 
 /// [`ClientInfo`] will contain messages. These are received from the server
 /// and _can_ be displayed by the UI. Not all messages need to be displayed.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Message {
     OpponentSelectsTarget,
     ClientMissedOpponent(logic::Position),
@@ -84,4 +436,6 @@ pub enum Message {
     OpponentHitClient(logic::Position),
     OpponentShipSunk(u8),
     ClientShipSunk(u8),
+    ClientChat(String),
+    OpponentChat(String),
 }
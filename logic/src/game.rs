@@ -0,0 +1,191 @@
+/// Signals a command that is against the rules in the current [`Phase`]
+#[derive(thiserror::Error, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CommandError {
+    #[error("fleet already placed")]
+    AlreadyPlaced,
+    #[error("fleets not placed yet")]
+    NotStarted,
+    #[error("not the turn of the player")]
+    NotYourTurn,
+    #[error("match is over")]
+    Over,
+    #[error("{0}")]
+    AlreadyHit(#[from] crate::board::AlreadyHitError),
+}
+
+/// One of the two players of a [`Game`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Player {
+    One,
+    Two,
+}
+
+impl Player {
+    pub fn other(self) -> Player {
+        match self {
+            Player::One => Player::Two,
+            Player::Two => Player::One,
+        }
+    }
+
+    /// `0` for the first and `1` for the second player
+    pub fn index(self) -> usize {
+        match self {
+            Player::One => 0,
+            Player::Two => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Waiting for the fleets of both players
+    Placing,
+    /// The player may fire
+    Turn(Player),
+    /// The player has won
+    Over(Player),
+}
+
+/// What happened due to a command. Both players are meant to learn about
+/// every event, each from their own perspective.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The turn passes to the other player
+    Miss {
+        shooter: Player,
+        target: crate::Position,
+    },
+    /// The shooter may fire again
+    Hit {
+        shooter: Player,
+        target: crate::Position,
+    },
+    /// The last untouched part of `ship` was hit, the shooter may fire again
+    Sunk {
+        shooter: Player,
+        target: crate::Position,
+        ship: crate::ship::Ship,
+    },
+    /// The match is over, as all ships of the loser sunk or they surrendered
+    Victory { winner: Player, surrendered: bool },
+}
+
+impl Event {
+    /// What a shot hit, `None` for a victory
+    pub fn attack_info(&self) -> Option<crate::board::AttackInfo> {
+        match *self {
+            Event::Miss { .. } => Some(crate::board::AttackInfo::Miss),
+            Event::Hit { .. } => Some(crate::board::AttackInfo::Hit(None)),
+            Event::Sunk { ship, .. } => Some(crate::board::AttackInfo::Hit(Some(ship))),
+            Event::Victory { .. } => None,
+        }
+    }
+}
+
+/// The rules of a single match, without any input or output. Every command
+/// either gets rejected, leaving the game untouched, or returns the events
+/// it caused.
+#[derive(Debug, Clone)]
+pub struct Game {
+    /// The board of every player, which the other one fires at
+    boards: [Option<crate::Board>; 2],
+    first: Player,
+    phase: Phase,
+}
+
+impl Game {
+    /// A match in which `first` fires first, once both fleets are placed
+    pub fn new(first: Player) -> Game {
+        Game {
+            boards: [None, None],
+            first,
+            phase: Phase::Placing,
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// The board of the player, once their fleet is placed
+    pub fn board(&self, player: Player) -> Option<&crate::Board> {
+        self.boards[player.index()].as_ref()
+    }
+
+    pub fn place(
+        &mut self,
+        player: Player,
+        ships: crate::Ships,
+    ) -> Result<Vec<Event>, CommandError> {
+        match self.phase {
+            Phase::Placing => {}
+            Phase::Turn(_) => return Err(CommandError::AlreadyPlaced),
+            Phase::Over(_) => return Err(CommandError::Over),
+        }
+        let board = &mut self.boards[player.index()];
+        if board.is_some() {
+            return Err(CommandError::AlreadyPlaced);
+        }
+        *board = Some(crate::Board::from_ships(ships));
+
+        if self.boards.iter().all(Option::is_some) {
+            self.phase = Phase::Turn(self.first);
+        }
+        Ok(vec![])
+    }
+
+    pub fn fire(
+        &mut self,
+        shooter: Player,
+        target: crate::Position,
+    ) -> Result<Vec<Event>, CommandError> {
+        match self.phase {
+            Phase::Placing => return Err(CommandError::NotStarted),
+            Phase::Turn(player) if player != shooter => return Err(CommandError::NotYourTurn),
+            Phase::Turn(_) => {}
+            Phase::Over(_) => return Err(CommandError::Over),
+        }
+        let board = self.boards[shooter.other().index()]
+            .as_mut()
+            .expect("fleets are placed before the first turn");
+
+        match board.target(target)? {
+            crate::board::AttackInfo::Miss => {
+                self.phase = Phase::Turn(shooter.other());
+                Ok(vec![Event::Miss { shooter, target }])
+            }
+            crate::board::AttackInfo::Hit(None) => Ok(vec![Event::Hit { shooter, target }]),
+            crate::board::AttackInfo::Hit(Some(ship)) => {
+                let sunk = Event::Sunk {
+                    shooter,
+                    target,
+                    ship,
+                };
+                if !board.is_all_sunken() {
+                    return Ok(vec![sunk]);
+                }
+                self.phase = Phase::Over(shooter);
+                Ok(vec![
+                    sunk,
+                    Event::Victory {
+                        winner: shooter,
+                        surrendered: false,
+                    },
+                ])
+            }
+        }
+    }
+
+    /// Gives the match up, at any point before it is over
+    pub fn surrender(&mut self, loser: Player) -> Result<Vec<Event>, CommandError> {
+        if let Phase::Over(_) = self.phase {
+            return Err(CommandError::Over);
+        }
+        self.phase = Phase::Over(loser.other());
+        Ok(vec![Event::Victory {
+            winner: loser.other(),
+            surrendered: true,
+        }])
+    }
+}
@@ -1,8 +1,10 @@
 pub mod board;
+pub mod game;
 pub mod position;
 pub mod ship;
 
 pub use board::Board;
+pub use game::Game;
 pub use position::Position;
 pub use ship::Ships;
 
@@ -16,6 +18,8 @@ pub enum Error {
     ShipCollection(#[from] ship::ShipCollectionError),
     #[error("logic :: board :: {0}")]
     Board(#[from] board::AlreadyHitError),
+    #[error("logic :: game :: {0}")]
+    Game(#[from] game::CommandError),
 }
 
 #[cfg(test)]
@@ -44,6 +48,67 @@ mod tests {
         assert_eq!(position, Err(position::OutOfBoundsError));
     }
 
+    #[test]
+    fn parse_positions() {
+        for byte in 0..=255 {
+            if let Ok(position) = position::Position::try_from_byte(byte) {
+                assert_eq!(position.to_string().parse(), Ok(position));
+            }
+        }
+        let position = position::Position::try_from_coords((4, 9)).unwrap();
+        assert_eq!(" e10 ".parse(), Ok(position));
+
+        use position::ParseError;
+        assert_eq!("".parse::<Position>(), Err(ParseError::Empty));
+        assert_eq!("K3".parse::<Position>(), Err(ParseError::Column('K')));
+        assert_eq!("A0".parse::<Position>(), Err(ParseError::Row("0".into())));
+        assert_eq!("A11".parse::<Position>(), Err(ParseError::Row("11".into())));
+        assert_eq!("A".parse::<Position>(), Err(ParseError::Row("".into())));
+    }
+
+    #[test]
+    fn parse_ships() {
+        let pos = |coords| position::Position::try_from_coords(coords).unwrap();
+        for ship in fleet() {
+            assert_eq!(ship.to_string().parse(), Ok(ship));
+        }
+        let ship = ship::Ship::try_from(ship::ShipPlan::Vertical {
+            pos: pos((1, 1)),
+            length: 5,
+        })
+        .unwrap();
+        assert_eq!(ship.to_string(), "B2-B6");
+        assert_eq!("b2-B6".parse(), Ok(ship));
+        for length in [0, 1] {
+            assert_eq!(
+                ship::Ship::try_from(ship::ShipPlan::Horizontal {
+                    pos: pos((1, 1)),
+                    length
+                }),
+                Err(ship::OutOfBoundsError)
+            );
+        }
+
+        use ship::{ParseError, Ship};
+        assert_eq!("B2".parse::<Ship>(), Err(ParseError::Format("B2".into())));
+        assert_eq!(
+            "B2-K2".parse::<Ship>(),
+            Err(ParseError::Position(position::ParseError::Column('K')))
+        );
+        assert_eq!(
+            "B6-B2".parse::<Ship>(),
+            Err(ParseError::Line("B6-B2".into()))
+        );
+        assert_eq!(
+            "B2-C3".parse::<Ship>(),
+            Err(ParseError::Line("B2-C3".into()))
+        );
+        assert_eq!(
+            "B2-B2".parse::<Ship>(),
+            Err(ParseError::Line("B2-B2".into()))
+        );
+    }
+
     #[test]
     fn ship_area_iterator() {
         let ship_plan = ship::ShipPlan::Horizontal {
@@ -179,4 +244,118 @@ mod tests {
             ship::Ships::try_from(ships)
         );
     }
+
+    /// A valid fleet, one ship of every length
+    fn fleet() -> ship::Ships {
+        let ship = |plan| ship::Ship::try_from(plan).unwrap();
+        let pos = |coords| position::Position::try_from_coords(coords).unwrap();
+        ship::Ships::try_from([
+            ship(ship::ShipPlan::Horizontal {
+                pos: pos((0, 1)),
+                length: 5,
+            }),
+            ship(ship::ShipPlan::Horizontal {
+                pos: pos((7, 2)),
+                length: 2,
+            }),
+            ship(ship::ShipPlan::Horizontal {
+                pos: pos((1, 6)),
+                length: 3,
+            }),
+            ship(ship::ShipPlan::Vertical {
+                pos: pos((5, 4)),
+                length: 4,
+            }),
+            ship(ship::ShipPlan::Vertical {
+                pos: pos((7, 7)),
+                length: 3,
+            }),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn game_rules() {
+        use game::{CommandError, Event, Phase, Player};
+
+        let mut game = Game::new(Player::Two);
+        let water = position::Position::try_from_coords((9, 9)).unwrap();
+        assert_eq!(game.fire(Player::Two, water), Err(CommandError::NotStarted));
+        assert_eq!(game.place(Player::One, fleet()), Ok(vec![]));
+        assert_eq!(
+            game.place(Player::One, fleet()),
+            Err(CommandError::AlreadyPlaced)
+        );
+        assert_eq!(game.phase(), Phase::Placing);
+        assert_eq!(game.place(Player::Two, fleet()), Ok(vec![]));
+        assert_eq!(game.phase(), Phase::Turn(Player::Two));
+
+        // a miss passes the turn on
+        assert_eq!(
+            game.fire(Player::One, water),
+            Err(CommandError::NotYourTurn)
+        );
+        assert_eq!(
+            game.fire(Player::Two, water),
+            Ok(vec![Event::Miss {
+                shooter: Player::Two,
+                target: water
+            }])
+        );
+        assert_eq!(game.phase(), Phase::Turn(Player::One));
+
+        // a hit lets the shooter go again
+        let mut targets = fleet().into_iter().flatten();
+        let target = targets.next().unwrap();
+        assert_eq!(
+            game.fire(Player::One, target),
+            Ok(vec![Event::Hit {
+                shooter: Player::One,
+                target
+            }])
+        );
+        assert_eq!(
+            game.fire(Player::One, target),
+            Err(CommandError::AlreadyHit(board::AlreadyHitError))
+        );
+        assert_eq!(game.phase(), Phase::Turn(Player::One));
+
+        let mut sunk = 0;
+        let mut events = vec![];
+        for target in targets {
+            events = game.fire(Player::One, target).unwrap();
+            sunk += events
+                .iter()
+                .filter(|event| matches!(event, Event::Sunk { .. }))
+                .count();
+        }
+        assert_eq!(sunk, 5);
+        assert_eq!(
+            events.last(),
+            Some(&Event::Victory {
+                winner: Player::One,
+                surrendered: false
+            })
+        );
+        assert_eq!(game.phase(), Phase::Over(Player::One));
+        assert_eq!(game.fire(Player::One, water), Err(CommandError::Over));
+        assert_eq!(game.surrender(Player::Two), Err(CommandError::Over));
+        assert!(game.board(Player::Two).unwrap().is_all_sunken());
+    }
+
+    #[test]
+    fn game_surrender() {
+        use game::{Event, Phase, Player};
+
+        let mut game = Game::new(Player::One);
+        assert_eq!(
+            game.surrender(Player::One),
+            Ok(vec![Event::Victory {
+                winner: Player::Two,
+                surrendered: true
+            }])
+        );
+        assert_eq!(game.phase(), Phase::Over(Player::Two));
+        assert!(game.board(Player::One).is_none());
+    }
 }
@@ -2,6 +2,18 @@
 #[error("position outside of bounds")]
 pub struct OutOfBoundsError;
 
+#[derive(thiserror::Error, Debug, PartialEq, Eq, Clone)]
+pub enum ParseError {
+    #[error("position is empty")]
+    Empty,
+    #[error("column `{0}` not in A-J")]
+    Column(char),
+    #[error("row `{0}` not in 1-10")]
+    Row(String),
+}
+
+const COLUMNS: [char; 10] = ['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J'];
+
 /// 2x4 byte unsigned integer vector
 /// constraint to `x < 10 && y < 10`
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -71,18 +83,40 @@ impl Position {
 
 impl std::fmt::Display for Position {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        const X_MAP: [char; 10] = ['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J'];
         const Y_MAP: [&str; 10] = ["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"];
         let (x, y) = self.to_coords();
         write!(
             f,
             "{:>width$}",
-            format!("{}{}", X_MAP[x as usize], Y_MAP[y as usize]),
+            format!("{}{}", COLUMNS[x as usize], Y_MAP[y as usize]),
             width = f.width().unwrap_or(0)
         )
     }
 }
 
+/// Parses a position as it is displayed, like `E7`, ignoring case and
+/// surrounding whitespace
+impl std::str::FromStr for Position {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.trim().chars();
+        let column = chars.next().ok_or(ParseError::Empty)?;
+        let x = COLUMNS
+            .iter()
+            .position(|&c| c == column.to_ascii_uppercase())
+            .ok_or(ParseError::Column(column))?;
+        let row = chars.as_str();
+        let y = row
+            .parse::<u8>()
+            .ok()
+            .and_then(|y| y.checked_sub(1))
+            .filter(|&y| y < 10)
+            .ok_or_else(|| ParseError::Row(row.to_string()))?;
+        Ok(Position::try_from_coords((x as u8, y)).expect("column and row are checked"))
+    }
+}
+
 impl<T> std::ops::Index<crate::Position> for [[T; 10]; 10] {
     type Output = T;
 
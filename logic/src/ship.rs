@@ -1,5 +1,5 @@
 #[derive(thiserror::Error, Debug, PartialEq, Eq, Clone, Copy)]
-#[error("ship body is out of bounds")]
+#[error("ship body is out of bounds, or shorter than two")]
 pub struct OutOfBoundsError;
 
 /// Error that signals if any step in the ship collection process has gone wrong.
@@ -14,6 +14,16 @@ pub enum ShipCollectionError {
     InvalidShipLengths,
 }
 
+#[derive(thiserror::Error, Debug, PartialEq, Eq, Clone)]
+pub enum ParseError {
+    #[error("ship `{0}` not written like `B2-B6`")]
+    Format(String),
+    #[error("{0}")]
+    Position(#[from] crate::position::ParseError),
+    #[error("ship `{0}` not a row or column, from its first to its last position")]
+    Line(String),
+}
+
 /// Plan how a ship could be positioned.
 /// May be an invalid position
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -22,7 +32,8 @@ pub enum ShipPlan {
     Vertical { pos: crate::Position, length: u8 },
 }
 
-/// A ship that is definetly within the bounds of the game board
+/// A ship that is definetly within the bounds of the game board, and at least
+/// two long
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Ship(ShipPlan);
 
@@ -30,11 +41,11 @@ impl TryFrom<ShipPlan> for Ship {
     type Error = OutOfBoundsError;
 
     fn try_from(ship: ShipPlan) -> Result<Self, Self::Error> {
-        if match ship {
-            ShipPlan::Horizontal { pos, length } => pos.to_coords().0 + length,
-            ShipPlan::Vertical { pos, length } => pos.to_coords().1 + length,
-        } <= 10
-        {
+        let (start, length) = match ship {
+            ShipPlan::Horizontal { pos, length } => (pos.to_coords().0, length),
+            ShipPlan::Vertical { pos, length } => (pos.to_coords().1, length),
+        };
+        if length >= 2 && start + length <= 10 {
             Ok(Ship(ship))
         } else {
             Err(OutOfBoundsError)
@@ -54,6 +65,50 @@ impl Ship {
     }
 }
 
+/// Writes the ship as its first and last position, like `B2-B6`
+impl std::fmt::Display for Ship {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (pos, (dx, dy)) = match self.0 {
+            ShipPlan::Horizontal { pos, length } => (pos, (length - 1, 0)),
+            ShipPlan::Vertical { pos, length } => (pos, (0, length - 1)),
+        };
+        let (x, y) = pos.to_coords();
+        let end =
+            crate::Position::try_from_coords((x + dx, y + dy)).expect("ships are within the board");
+        write!(f, "{pos}-{end}")
+    }
+}
+
+/// Parses a ship as it is displayed, like `B2-B6`
+impl std::str::FromStr for Ship {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pos, end) = s
+            .split_once('-')
+            .ok_or_else(|| ParseError::Format(s.to_string()))?;
+        let (pos, end) = (
+            pos.parse::<crate::Position>()?,
+            end.parse::<crate::Position>()?,
+        );
+        let ((x1, y1), (x2, y2)) = (pos.to_coords(), end.to_coords());
+        let plan = if y1 == y2 && x1 < x2 {
+            ShipPlan::Horizontal {
+                pos,
+                length: x2 - x1 + 1,
+            }
+        } else if x1 == x2 && y1 < y2 {
+            ShipPlan::Vertical {
+                pos,
+                length: y2 - y1 + 1,
+            }
+        } else {
+            return Err(ParseError::Line(s.to_string()));
+        };
+        Ok(Ship::try_from(plan).expect("both ends are within the board"))
+    }
+}
+
 /// Iterates over every position the ship occupies
 /// These positions may not be ascending in order
 pub struct ShipAreaIterator(ShipPlan);
@@ -103,6 +158,9 @@ impl Iterator for ShipAreaIterator {
     }
 }
 
+/// Lengths of the ships every fleet consists of, longest first
+pub const SHIP_LENGTHS: [u8; 5] = [5, 4, 3, 3, 2];
+
 /// A collection of five ships that are definetly valid and not overlapping
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Ships([Ship; 5]);
@@ -111,8 +169,6 @@ impl TryFrom<[Ship; 5]> for Ships {
     type Error = ShipCollectionError;
 
     fn try_from(ships: [Ship; 5]) -> Result<Self, Self::Error> {
-        const SHIP_LENGTHS: [u8; 5] = [2, 3, 3, 4, 5];
-
         let mut ship_map = [[false; 10]; 10];
         let mut ship_length_map = [false; 5];
         for ship in ships {
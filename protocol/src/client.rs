@@ -6,22 +6,50 @@ pub enum Error {
     InvalidLogic(#[from] logic::Error),
 }
 
+/// Who the player claims to be. Only the name is shown to others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// See [`crate::is_valid_name`]
+    pub name: String,
+    /// Protects the name, at most [`crate::MAX_PASSWORD_LEN`] bytes long.
+    /// The first password used with a name claims it.
+    pub password: Option<String>,
+}
+
 /// Message that gets send from the client and is received from the server
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum Message {
-    HandShake,
+    HandShake(Identity),
 
     Acknowledge,
+    Surrender,
+    /// Text for the opponent, at most [`crate::MAX_CHAT_LEN`] bytes long
+    Chat(String),
 
     ReturnShips(logic::Ships),
     ReturnTarget(logic::Position),
+    ReturnRematch(bool),
+
+    RequestLeaderboard,
 }
 
 impl crate::raw::IntoMessage for Message {
     fn into_raw_message(self) -> crate::raw::Message {
         match self {
-            Message::HandShake => crate::raw::HANDSHAKE.to_message(),
+            Message::HandShake(identity) => crate::raw::Message {
+                type_marker: crate::raw::TYPE_IDENTIFY,
+                body: [identity.name.len() as u8]
+                    .into_iter()
+                    .chain(identity.name.into_bytes())
+                    .chain(identity.password.unwrap_or_default().into_bytes())
+                    .collect(),
+            },
             Message::Acknowledge => crate::raw::ACKNOWLEDGE.to_message(),
+            Message::Surrender => crate::raw::SURRENDER.to_message(),
+            Message::Chat(text) => crate::raw::Message {
+                type_marker: crate::raw::TYPE_CHAT,
+                body: text.into_bytes(),
+            },
             Message::ReturnShips(ships) => crate::raw::Message {
                 type_marker: crate::raw::TYPE_REQ_RET_SHIPS,
                 body: ships
@@ -40,6 +68,11 @@ impl crate::raw::IntoMessage for Message {
                 type_marker: crate::raw::TYPE_REQ_RET_TARGET,
                 body: vec![target.to_byte()],
             },
+            Message::ReturnRematch(accept) => crate::raw::Message {
+                type_marker: crate::raw::TYPE_REQ_RET_REMATCH,
+                body: vec![accept as u8],
+            },
+            Message::RequestLeaderboard => crate::raw::REQUEST_LEADERBOARD.to_message(),
         }
     }
 }
@@ -49,8 +82,30 @@ impl crate::raw::TryFromMessage for Message {
 
     fn try_from_raw_message(message: crate::raw::Message) -> Result<Self, Self::Error> {
         match message.as_match() {
-            crate::raw::HANDSHAKE => Ok(Message::HandShake),
+            crate::raw::MessageMatch {
+                type_marker: crate::raw::TYPE_IDENTIFY,
+                body: [name_len, rest @ ..],
+            } if rest.len() >= *name_len as usize => {
+                let (name, password) = rest.split_at(*name_len as usize);
+                let name = String::from_utf8(name.to_vec()).map_err(|_| Error::InvalidBytes)?;
+                let password =
+                    String::from_utf8(password.to_vec()).map_err(|_| Error::InvalidBytes)?;
+                if !crate::is_valid_name(&name) || password.len() > crate::MAX_PASSWORD_LEN {
+                    return Err(Error::InvalidBytes);
+                }
+                Ok(Message::HandShake(Identity {
+                    name,
+                    password: (!password.is_empty()).then_some(password),
+                }))
+            }
             crate::raw::ACKNOWLEDGE => Ok(Message::Acknowledge),
+            crate::raw::SURRENDER => Ok(Message::Surrender),
+            crate::raw::MessageMatch {
+                type_marker: crate::raw::TYPE_CHAT,
+                body,
+            } => Ok(Message::Chat(
+                crate::chat_from_bytes(body).ok_or(Error::InvalidBytes)?,
+            )),
             crate::raw::MessageMatch {
                 type_marker: crate::raw::TYPE_REQ_RET_SHIPS,
                 body,
@@ -84,6 +139,11 @@ impl crate::raw::TryFromMessage for Message {
             } => Ok(Message::ReturnTarget(
                 pos.clone().try_into().map_err(logic::Error::from)?,
             )),
+            crate::raw::MessageMatch {
+                type_marker: crate::raw::TYPE_REQ_RET_REMATCH,
+                body: [accept @ (0 | 1)],
+            } => Ok(Message::ReturnRematch(*accept == 1)),
+            crate::raw::REQUEST_LEADERBOARD => Ok(Message::RequestLeaderboard),
             _ => Err(Error::InvalidBytes),
         }
     }
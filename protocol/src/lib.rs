@@ -2,6 +2,7 @@ use tokio::io;
 
 pub mod client;
 pub mod server;
+pub mod session;
 
 pub(crate) mod raw;
 
@@ -22,37 +23,363 @@ impl<M: raw::TryFromMessage> Error<M> {
     }
 }
 
-pub async fn write<R, M>(writer: &mut R, message: M) -> Result<(), io::Error>
-where
-    R: io::AsyncWriteExt + std::marker::Unpin,
-    M: raw::IntoMessage,
-{
-    let message: raw::Message = message.into_raw_message();
-    writer.write_u8(message.type_marker).await?;
-    writer.write_u32(message.body.len() as u32).await?;
-    writer.write_all(&message.body).await?;
-    writer.flush().await?;
+/// Maximum length of a chat message in bytes
+pub const MAX_CHAT_LEN: usize = 200;
 
-    Ok(())
+/// Shortens the chat message to [`MAX_CHAT_LEN`] bytes, without splitting a character
+pub fn truncate_chat(text: &mut String) {
+    if text.len() > MAX_CHAT_LEN {
+        let end = (0..=MAX_CHAT_LEN)
+            .rev()
+            .find(|&i| text.is_char_boundary(i))
+            .unwrap_or(0);
+        text.truncate(end);
+    }
 }
 
-pub async fn read<R, M>(reader: &mut R) -> Result<M, Error<M>>
-where
-    R: io::AsyncReadExt + std::marker::Unpin,
-    M: raw::TryFromMessage,
-{
-    let type_marker = reader.read_u8().await?;
-    let length_marker = reader.read_u32().await?;
-    let mut body = vec![0; length_marker as usize];
-    reader.read_exact(&mut body).await?;
+/// Maximum length of a player name in bytes
+pub const MAX_NAME_LEN: usize = 16;
+/// Maximum length of a password in bytes
+pub const MAX_PASSWORD_LEN: usize = 64;
 
-    let message = raw::Message { type_marker, body };
+/// Player names consist of 1 to [`MAX_NAME_LEN`] ascii letters, digits, `-` and `_`
+pub fn is_valid_name(name: &str) -> bool {
+    (1..=MAX_NAME_LEN).contains(&name.len())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
 
-    M::try_from_raw_message(message).map_err(Error::<M>::from_prot_err)
+/// Decodes the body of a chat message
+fn chat_from_bytes(body: &[u8]) -> Option<String> {
+    if body.len() > MAX_CHAT_LEN {
+        return None;
+    }
+    String::from_utf8(body.to_vec()).ok()
 }
 
-// TODO
 #[cfg(test)]
 mod tests {
     use super::*;
+    use raw::{IntoMessage, TryFromMessage};
+
+    fn round_trip<M: IntoMessage + TryFromMessage>(message: M) -> M {
+        M::try_from_raw_message(message.into_raw_message()).unwrap()
+    }
+
+    #[test]
+    fn frame_reader_partial() {
+        let mut bytes = session::encode(
+            session::Correlation::Request(7),
+            server::Message::RequestTarget.into_raw_message(),
+        );
+        bytes.extend(session::encode(
+            session::Correlation::None,
+            server::Message::InformLoss.into_raw_message(),
+        ));
+
+        let mut reader = session::FrameReader::new(&b""[..]);
+        reader.buf.extend_from_slice(&bytes[..3]);
+        assert!(reader.next_frame().is_none());
+        reader.buf.extend_from_slice(&bytes[3..]);
+        assert_eq!(
+            reader.next_frame().unwrap().unwrap(),
+            (
+                session::Correlation::Request(7),
+                raw::REQUEST_TARGET.to_message()
+            )
+        );
+        assert_eq!(
+            reader.next_frame().unwrap().unwrap(),
+            (session::Correlation::None, raw::INFORM_LOSS.to_message())
+        );
+        assert!(reader.next_frame().is_none());
+    }
+
+    #[tokio::test]
+    async fn session_requests() {
+        let (server_stream, client_stream) = io::duplex(1024);
+        let (server, _) = session::start::<server::Message, client::Message, _>(
+            server_stream,
+            session::Heartbeat::default(),
+        );
+        let (client, mut client_incoming) = session::start::<client::Message, server::Message, _>(
+            client_stream,
+            session::Heartbeat::default(),
+        );
+
+        // a dropped request does not mix up the responses of later ones
+        let dropped = server.request(server::Message::RequestShips);
+        tokio::select! {
+            biased;
+            _ = dropped => unreachable!("the ship request is not answered yet"),
+            _ = std::future::ready(()) => {}
+        }
+        let Ok(session::Incoming::Request(dropped_id, server::Message::RequestShips)) =
+            client_incoming.recv().await
+        else {
+            panic!("expected ship request")
+        };
+
+        let request = server.request(server::Message::RequestTarget);
+        let answer = async {
+            let Ok(session::Incoming::Request(id, server::Message::RequestTarget)) =
+                client_incoming.recv().await
+            else {
+                panic!("expected target request")
+            };
+            client.send(client::Message::Surrender).unwrap();
+            client
+                .respond(dropped_id, client::Message::Acknowledge)
+                .unwrap();
+            client
+                .respond(
+                    id,
+                    client::Message::ReturnTarget(
+                        logic::Position::try_from_coords((1, 2)).unwrap(),
+                    ),
+                )
+                .unwrap();
+        };
+        let (response, ()) = tokio::join!(request, answer);
+        assert!(matches!(
+            response,
+            Ok(client::Message::ReturnTarget(pos)) if pos == logic::Position::try_from_coords((1, 2)).unwrap()
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn heartbeat_keeps_alive() {
+        let (server_stream, client_stream) = io::duplex(1024);
+        let heartbeat = session::Heartbeat::default();
+        let (server, _) =
+            session::start::<server::Message, client::Message, _>(server_stream, heartbeat);
+        let (client, mut client_incoming) =
+            session::start::<client::Message, server::Message, _>(client_stream, heartbeat);
+
+        tokio::time::sleep(heartbeat.timeout() * 3).await;
+        assert!(server.health().latency.is_some());
+        assert!(client.health().latency.is_some());
+
+        let answer = async {
+            let Ok(session::Incoming::Request(id, _)) = client_incoming.recv().await else {
+                panic!("expected request")
+            };
+            client.respond(id, client::Message::Acknowledge).unwrap();
+        };
+        let (response, ()) = tokio::join!(server.request(server::Message::InformLoss), answer);
+        assert!(matches!(response, Ok(client::Message::Acknowledge)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn heartbeat_dead_peer() {
+        let (stream, _silent) = io::duplex(1024);
+        let heartbeat = session::Heartbeat::default();
+        let (server, mut server_incoming) =
+            session::start::<server::Message, client::Message, _>(stream, heartbeat);
+
+        let (response, received) = tokio::join!(
+            server.request(server::Message::RequestShips),
+            server_incoming.recv(),
+        );
+        assert!(matches!(
+            response,
+            Err(Error::Io(err)) if err.kind() == io::ErrorKind::ConnectionAborted
+        ));
+        assert!(matches!(
+            received,
+            Err(Error::Io(err)) if err.kind() == io::ErrorKind::TimedOut
+        ));
+        assert!(server.health().latency.is_none());
+        assert!(server.health().last_seen.elapsed() >= heartbeat.timeout());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn session_dropped() {
+        let (server_stream, client_stream) = io::duplex(1024);
+        let heartbeat = session::Heartbeat::default();
+        let (server, server_incoming) =
+            session::start::<server::Message, client::Message, _>(server_stream, heartbeat);
+        let (_client, mut client_incoming) =
+            session::start::<client::Message, server::Message, _>(client_stream, heartbeat);
+
+        // the heartbeat alone does not keep the connection open
+        drop((server, server_incoming));
+        assert!(matches!(
+            client_incoming.recv().await,
+            Err(Error::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn heartbeat_checked() {
+        let secs = std::time::Duration::from_secs;
+        assert_eq!(
+            session::Heartbeat::new(secs(0), secs(5)),
+            Err(session::InvalidHeartbeat::ZeroInterval)
+        );
+        assert_eq!(
+            session::Heartbeat::new(secs(5), secs(5)),
+            Err(session::InvalidHeartbeat::TimeoutNotLonger)
+        );
+        let heartbeat = session::Heartbeat::new(secs(1), secs(3)).unwrap();
+        assert_eq!(
+            (heartbeat.interval(), heartbeat.timeout()),
+            (secs(1), secs(3))
+        );
+    }
+
+    #[test]
+    fn rematch_messages() {
+        assert!(matches!(
+            round_trip(server::Message::RequestRematch),
+            server::Message::RequestRematch
+        ));
+        assert!(matches!(
+            round_trip(client::Message::ReturnRematch(true)),
+            client::Message::ReturnRematch(true)
+        ));
+        assert!(matches!(
+            round_trip(client::Message::ReturnRematch(false)),
+            client::Message::ReturnRematch(false)
+        ));
+    }
+
+    #[test]
+    fn surrender_message() {
+        assert!(matches!(
+            round_trip(client::Message::Surrender),
+            client::Message::Surrender
+        ));
+    }
+
+    #[test]
+    fn chat_messages() {
+        assert!(matches!(
+            round_trip(client::Message::Chat("hello there".to_string())),
+            client::Message::Chat(text) if text == "hello there"
+        ));
+        assert!(matches!(
+            round_trip(server::Message::InformChat("general kenobi".to_string())),
+            server::Message::InformChat(text) if text == "general kenobi"
+        ));
+
+        let too_long = client::Message::Chat("a".repeat(MAX_CHAT_LEN + 1)).into_raw_message();
+        assert_eq!(
+            client::Message::try_from_raw_message(too_long).err(),
+            Some(client::Error::InvalidBytes)
+        );
+    }
+
+    #[test]
+    fn chat_truncation() {
+        let mut text = "ä".repeat(MAX_CHAT_LEN);
+        truncate_chat(&mut text);
+        assert_eq!(text.len(), MAX_CHAT_LEN);
+
+        let mut text = format!("a{}", "ä".repeat(MAX_CHAT_LEN));
+        truncate_chat(&mut text);
+        assert_eq!(text.len(), MAX_CHAT_LEN - 1);
+    }
+
+    #[test]
+    fn identity_messages() {
+        for password in [None, Some("hunter2".to_string())] {
+            let identity = client::Identity {
+                name: "ziel_player-1".to_string(),
+                password,
+            };
+            assert!(matches!(
+                round_trip(client::Message::HandShake(identity.clone())),
+                client::Message::HandShake(i) if i == identity
+            ));
+        }
+
+        for name in ["", "with space", "ä", &"a".repeat(MAX_NAME_LEN + 1)] {
+            let invalid = client::Message::HandShake(client::Identity {
+                name: name.to_string(),
+                password: None,
+            })
+            .into_raw_message();
+            assert_eq!(
+                client::Message::try_from_raw_message(invalid).err(),
+                Some(client::Error::InvalidBytes)
+            );
+        }
+
+        assert!(matches!(
+            round_trip(server::Message::RejectHandshake(
+                server::Rejection::WrongPassword
+            )),
+            server::Message::RejectHandshake(server::Rejection::WrongPassword)
+        ));
+    }
+
+    #[test]
+    fn leaderboard_messages() {
+        assert!(matches!(
+            round_trip(client::Message::RequestLeaderboard),
+            client::Message::RequestLeaderboard
+        ));
+
+        let standings = vec![
+            server::Standing {
+                name: "first".to_string(),
+                rating: 1612,
+                wins: 12,
+                losses: 3,
+            },
+            server::Standing {
+                name: "second".to_string(),
+                rating: 1488,
+                wins: 0,
+                losses: 1,
+            },
+        ];
+        assert!(matches!(
+            round_trip(server::Message::ReturnLeaderboard(standings.clone())),
+            server::Message::ReturnLeaderboard(s) if s == standings
+        ));
+        assert!(matches!(
+            round_trip(server::Message::ReturnLeaderboard(vec![])),
+            server::Message::ReturnLeaderboard(s) if s.is_empty()
+        ));
+    }
+
+    #[test]
+    fn queue_messages() {
+        for estimated_wait in [None, Some(0), Some(90)] {
+            let status = server::QueueStatus {
+                position: 3,
+                estimated_wait,
+            };
+            assert!(matches!(
+                round_trip(server::Message::InformQueue(status)),
+                server::Message::InformQueue(s) if s == status
+            ));
+        }
+    }
+
+    #[test]
+    fn score_message() {
+        let score = server::Score {
+            client: 2,
+            opponent: 1,
+            best_of: Some(5),
+        };
+        assert!(matches!(
+            round_trip(server::Message::InformScore(score)),
+            server::Message::InformScore(s) if s == score
+        ));
+
+        let score = server::Score {
+            client: 0,
+            opponent: 3,
+            best_of: None,
+        };
+        assert!(matches!(
+            round_trip(server::Message::InformScore(score)),
+            server::Message::InformScore(s) if s == score
+        ));
+    }
 }
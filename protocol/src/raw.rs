@@ -58,8 +58,19 @@ pub const TERMINATE: MessageMatch = MessageMatch {
     body: b"TERM",
 };
 
+pub const SURRENDER: MessageMatch = MessageMatch {
+    type_marker: 3,
+    body: b"SURRENDER",
+};
+
+pub const TYPE_CHAT: u8 = 4;
+pub const TYPE_IDENTIFY: u8 = 5;
+pub const TYPE_REJECT: u8 = 6;
+
 pub const TYPE_REQ_RET_SHIPS: u8 = 100;
 pub const TYPE_REQ_RET_TARGET: u8 = 101;
+pub const TYPE_REQ_RET_REMATCH: u8 = 102;
+pub const TYPE_REQ_RET_LEADERBOARD: u8 = 103;
 
 pub const REQUEST_SHIPS: MessageMatch = MessageMatch {
     type_marker: TYPE_REQ_RET_SHIPS,
@@ -69,6 +80,14 @@ pub const REQUEST_TARGET: MessageMatch = MessageMatch {
     type_marker: TYPE_REQ_RET_TARGET,
     body: b"REQ TARG",
 };
+pub const REQUEST_REMATCH: MessageMatch = MessageMatch {
+    type_marker: TYPE_REQ_RET_REMATCH,
+    body: b"REQ REMA",
+};
+pub const REQUEST_LEADERBOARD: MessageMatch = MessageMatch {
+    type_marker: TYPE_REQ_RET_LEADERBOARD,
+    body: b"REQ LEAD",
+};
 
 pub const INFORM_TARGET_SELECTION: MessageMatch = MessageMatch {
     type_marker: 150,
@@ -89,3 +108,6 @@ pub const INFORM_VICTORY: MessageMatch = MessageMatch {
     type_marker: 155,
     body: b"VICTORY",
 };
+
+pub const TYPE_INFORM_SCORE: u8 = 156;
+pub const TYPE_INFORM_QUEUE: u8 = 157;
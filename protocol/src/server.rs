@@ -6,15 +6,62 @@ pub enum Error {
     InvalidLogic(#[from] logic::Error),
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Score of a series of matches played over the same connections
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Score {
+    /// matches won by the receiving client
+    pub client: u8,
+    /// matches won by the opponent of the receiving client
+    pub opponent: u8,
+    /// number of matches the series is played over, `None` if unlimited
+    pub best_of: Option<u8>,
+}
+
+/// Place of a client waiting in the matchmaking queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueStatus {
+    /// Starting at 1 for the client waiting the longest
+    pub position: u16,
+    /// Seconds until an opponent is likely found, `None` if unknown
+    pub estimated_wait: Option<u16>,
+}
+
+/// Why the server refused the handshake
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    #[error("invalid name")]
+    InvalidName,
+    #[error("wrong password")]
+    WrongPassword,
+    #[error("name already connected")]
+    AlreadyConnected,
+    #[error("not entered in the tournament")]
+    NotEntered,
+}
+
+/// A player on the leaderboard
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Standing {
+    pub name: String,
+    pub rating: u16,
+    pub wins: u16,
+    pub losses: u16,
+}
+
+/// Maximum number of players in a leaderboard
+pub const LEADERBOARD_LEN: usize = 10;
+
+#[derive(Debug, Clone)]
 pub enum Message {
     Handshake,
+    RejectHandshake(Rejection),
 
     Invalid,
     TerminateConnection,
 
     RequestShips,
     RequestTarget,
+    RequestRematch,
 
     InformTargetSelection,
     InformTargetMissClient(logic::Position),
@@ -25,16 +72,34 @@ pub enum Message {
     InformShipSunkenOpponent(logic::ship::Ship),
     InformLoss,
     InformVictory,
+    InformScore(Score),
+    /// Sent while the client waits for an opponent, no response is expected
+    InformQueue(QueueStatus),
+    /// Text sent by the opponent, at most [`crate::MAX_CHAT_LEN`] bytes long
+    InformChat(String),
+
+    /// The best rated players, at most [`LEADERBOARD_LEN`]
+    ReturnLeaderboard(Vec<Standing>),
 }
 
 impl crate::raw::IntoMessage for Message {
     fn into_raw_message(self) -> crate::raw::Message {
         match self {
             Message::Handshake => crate::raw::HANDSHAKE.to_message(),
+            Message::RejectHandshake(rejection) => crate::raw::Message {
+                type_marker: crate::raw::TYPE_REJECT,
+                body: vec![match rejection {
+                    Rejection::InvalidName => 0,
+                    Rejection::WrongPassword => 1,
+                    Rejection::AlreadyConnected => 2,
+                    Rejection::NotEntered => 3,
+                }],
+            },
             Message::Invalid => crate::raw::INVALID.to_message(),
             Message::TerminateConnection => crate::raw::TERMINATE.to_message(),
             Message::RequestShips => crate::raw::REQUEST_SHIPS.to_message(),
             Message::RequestTarget => crate::raw::REQUEST_TARGET.to_message(),
+            Message::RequestRematch => crate::raw::REQUEST_REMATCH.to_message(),
             Message::InformTargetSelection => crate::raw::INFORM_TARGET_SELECTION.to_message(),
             Message::InformTargetMissClient(pos) => crate::raw::Message {
                 type_marker: crate::raw::TYPE_INFORM_MISS,
@@ -76,6 +141,38 @@ impl crate::raw::IntoMessage for Message {
             },
             Message::InformLoss => crate::raw::INFORM_LOSS.to_message(),
             Message::InformVictory => crate::raw::INFORM_VICTORY.to_message(),
+            Message::InformScore(score) => crate::raw::Message {
+                type_marker: crate::raw::TYPE_INFORM_SCORE,
+                body: vec![score.client, score.opponent, score.best_of.unwrap_or(0)],
+            },
+            Message::InformQueue(status) => crate::raw::Message {
+                type_marker: crate::raw::TYPE_INFORM_QUEUE,
+                body: status
+                    .position
+                    .to_be_bytes()
+                    .into_iter()
+                    .chain(status.estimated_wait.unwrap_or(u16::MAX).to_be_bytes())
+                    .collect(),
+            },
+            Message::InformChat(text) => crate::raw::Message {
+                type_marker: crate::raw::TYPE_CHAT,
+                body: text.into_bytes(),
+            },
+            Message::ReturnLeaderboard(standings) => crate::raw::Message {
+                type_marker: crate::raw::TYPE_REQ_RET_LEADERBOARD,
+                body: standings
+                    .into_iter()
+                    .take(LEADERBOARD_LEN)
+                    .flat_map(|standing| {
+                        [standing.name.len() as u8]
+                            .into_iter()
+                            .chain(standing.name.into_bytes())
+                            .chain(standing.rating.to_be_bytes())
+                            .chain(standing.wins.to_be_bytes())
+                            .chain(standing.losses.to_be_bytes())
+                    })
+                    .collect(),
+            },
         }
     }
 }
@@ -86,10 +183,21 @@ impl crate::raw::TryFromMessage for Message {
     fn try_from_raw_message(message: crate::raw::Message) -> Result<Self, Self::Error> {
         match message.as_match() {
             crate::raw::HANDSHAKE => Ok(Message::Handshake),
+            crate::raw::MessageMatch {
+                type_marker: crate::raw::TYPE_REJECT,
+                body: [reason],
+            } => Ok(Message::RejectHandshake(match reason {
+                0 => Rejection::InvalidName,
+                1 => Rejection::WrongPassword,
+                2 => Rejection::AlreadyConnected,
+                3 => Rejection::NotEntered,
+                _ => return Err(Error::InvalidBytes),
+            })),
             crate::raw::INVALID => Ok(Message::Invalid),
             crate::raw::TERMINATE => Ok(Message::TerminateConnection),
             crate::raw::REQUEST_SHIPS => Ok(Message::RequestShips),
             crate::raw::REQUEST_TARGET => Ok(Message::RequestTarget),
+            crate::raw::REQUEST_REMATCH => Ok(Message::RequestRematch),
             crate::raw::INFORM_TARGET_SELECTION => Ok(Message::InformTargetSelection),
             crate::raw::MessageMatch {
                 type_marker: crate::raw::TYPE_INFORM_MISS,
@@ -149,9 +257,64 @@ impl crate::raw::TryFromMessage for Message {
                     .map_err(logic::Error::from)?,
                 ))
             }
+            crate::raw::MessageMatch {
+                type_marker: crate::raw::TYPE_CHAT,
+                body,
+            } => Ok(Message::InformChat(
+                crate::chat_from_bytes(body).ok_or(Error::InvalidBytes)?,
+            )),
             crate::raw::INFORM_LOSS => Ok(Message::InformLoss),
             crate::raw::INFORM_VICTORY => Ok(Message::InformVictory),
+            crate::raw::MessageMatch {
+                type_marker: crate::raw::TYPE_INFORM_SCORE,
+                body: [client, opponent, best_of],
+            } => Ok(Message::InformScore(Score {
+                client: *client,
+                opponent: *opponent,
+                best_of: match best_of {
+                    0 => None,
+                    n => Some(*n),
+                },
+            })),
+            crate::raw::MessageMatch {
+                type_marker: crate::raw::TYPE_INFORM_QUEUE,
+                body: [p1, p2, w1, w2],
+            } => Ok(Message::InformQueue(QueueStatus {
+                position: u16::from_be_bytes([*p1, *p2]),
+                estimated_wait: match u16::from_be_bytes([*w1, *w2]) {
+                    u16::MAX => None,
+                    wait => Some(wait),
+                },
+            })),
+            crate::raw::MessageMatch {
+                type_marker: crate::raw::TYPE_REQ_RET_LEADERBOARD,
+                body,
+            } => Ok(Message::ReturnLeaderboard(
+                standings_from_bytes(body).ok_or(Error::InvalidBytes)?,
+            )),
             _ => Err(Error::InvalidBytes),
         }
     }
 }
+
+fn standings_from_bytes(mut body: &[u8]) -> Option<Vec<Standing>> {
+    let mut standings = vec![];
+    while let [name_len, rest @ ..] = body {
+        let (name, rest) = rest.split_at_checked(*name_len as usize)?;
+        let [r1, r2, w1, w2, l1, l2, rest @ ..] = rest else {
+            return None;
+        };
+        let name = String::from_utf8(name.to_vec()).ok()?;
+        if !crate::is_valid_name(&name) || standings.len() == LEADERBOARD_LEN {
+            return None;
+        }
+        standings.push(Standing {
+            name,
+            rating: u16::from_be_bytes([*r1, *r2]),
+            wins: u16::from_be_bytes([*w1, *w2]),
+            losses: u16::from_be_bytes([*l1, *l2]),
+        });
+        body = rest;
+    }
+    Some(standings)
+}
@@ -0,0 +1,529 @@
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{atomic, Arc, Mutex},
+};
+
+use tokio::{
+    io::{self, AsyncReadExt, AsyncWriteExt},
+    sync::{mpsc, oneshot},
+    task, time,
+};
+
+use crate::raw;
+
+/// How a message relates to the other messages of the session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Correlation {
+    /// Sent on its own accord, no response is expected
+    None,
+    /// A response with the same id is expected
+    Request(u32),
+    /// The response to the request with the same id
+    Response(u32),
+    /// Heartbeat, answered with a pong of the same id. Carries no message.
+    Ping(u32),
+    /// The answer to the ping with the same id
+    Pong(u32),
+}
+
+impl Correlation {
+    fn to_bytes(self) -> (u8, u32) {
+        match self {
+            Correlation::None => (0, 0),
+            Correlation::Request(id) => (1, id),
+            Correlation::Response(id) => (2, id),
+            Correlation::Ping(id) => (3, id),
+            Correlation::Pong(id) => (4, id),
+        }
+    }
+
+    fn try_from_bytes(kind: u8, id: u32) -> Option<Correlation> {
+        match kind {
+            0 => Some(Correlation::None),
+            1 => Some(Correlation::Request(id)),
+            2 => Some(Correlation::Response(id)),
+            3 => Some(Correlation::Ping(id)),
+            4 => Some(Correlation::Pong(id)),
+            _ => None,
+        }
+    }
+}
+
+/// Identifies a received request, needed to respond to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestId(u32);
+
+/// A message received from the other side of the session
+#[derive(Debug)]
+pub enum Incoming<M> {
+    /// Sent on its own accord, no response is expected
+    Message(M),
+    /// Needs to be answered with [`Sender::respond`]
+    Request(RequestId, M),
+}
+
+/// How often the other side gets pinged, and how long it may stay silent
+/// before the connection counts as dead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heartbeat {
+    interval: time::Duration,
+    timeout: time::Duration,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum InvalidHeartbeat {
+    #[error("the heartbeat interval must not be zero")]
+    ZeroInterval,
+    #[error("the heartbeat timeout must be longer than the interval")]
+    TimeoutNotLonger,
+}
+
+impl Heartbeat {
+    /// Fails if the interval is zero, or the other side would count as dead
+    /// before it was even pinged
+    pub fn new(
+        interval: time::Duration,
+        timeout: time::Duration,
+    ) -> Result<Heartbeat, InvalidHeartbeat> {
+        if interval.is_zero() {
+            return Err(InvalidHeartbeat::ZeroInterval);
+        }
+        if timeout <= interval {
+            return Err(InvalidHeartbeat::TimeoutNotLonger);
+        }
+        Ok(Heartbeat { interval, timeout })
+    }
+
+    pub fn interval(&self) -> time::Duration {
+        self.interval
+    }
+
+    pub fn timeout(&self) -> time::Duration {
+        self.timeout
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Heartbeat {
+            interval: time::Duration::from_secs(5),
+            timeout: time::Duration::from_secs(20),
+        }
+    }
+}
+
+/// Health of the connection, as measured by the heartbeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Health {
+    /// Round trip time of the last answered ping
+    pub latency: Option<time::Duration>,
+    /// When the last frame of the other side was received
+    pub last_seen: time::Instant,
+}
+
+#[derive(Debug)]
+struct Monitor {
+    health: Health,
+    /// The ping waiting for its pong, and when it was sent
+    ping: Option<(u32, time::Instant)>,
+}
+
+type SharedMonitor = Arc<Mutex<Monitor>>;
+
+/// type marker, correlation kind, correlation id and body length
+const HEADER_LEN: usize = 10;
+
+pub(crate) fn encode(correlation: Correlation, message: raw::Message) -> Vec<u8> {
+    let (kind, id) = correlation.to_bytes();
+    let mut buf = Vec::with_capacity(HEADER_LEN + message.body.len());
+    buf.push(message.type_marker);
+    buf.push(kind);
+    buf.extend_from_slice(&id.to_be_bytes());
+    buf.extend_from_slice(&(message.body.len() as u32).to_be_bytes());
+    buf.extend_from_slice(&message.body);
+    buf
+}
+
+/// Reads frames while buffering partially received ones, so reading is cancel safe
+#[derive(Debug)]
+pub(crate) struct FrameReader<R> {
+    reader: R,
+    pub(crate) buf: Vec<u8>,
+}
+
+impl<R> FrameReader<R> {
+    pub(crate) fn new(reader: R) -> FrameReader<R> {
+        FrameReader {
+            reader,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Takes the next complete frame out of the buffer
+    pub(crate) fn next_frame(&mut self) -> Option<io::Result<(Correlation, raw::Message)>> {
+        let header = self.buf.get(..HEADER_LEN)?;
+        let [type_marker, kind, id @ .., _, _, _, _] = header else {
+            unreachable!("header has a fixed length")
+        };
+        let id = u32::from_be_bytes(id.try_into().expect("id has 4 bytes"));
+        let length = u32::from_be_bytes(header[6..].try_into().expect("length has 4 bytes"));
+        let end = HEADER_LEN + length as usize;
+        if self.buf.len() < end {
+            return None;
+        }
+
+        let Some(correlation) = Correlation::try_from_bytes(*kind, id) else {
+            return Some(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid correlation kind",
+            )));
+        };
+        let message = raw::Message {
+            type_marker: *type_marker,
+            body: self.buf[HEADER_LEN..end].to_vec(),
+        };
+        self.buf.drain(..end);
+        Some(Ok((correlation, message)))
+    }
+}
+
+impl<R> FrameReader<R>
+where
+    R: io::AsyncRead + std::marker::Unpin,
+{
+    pub(crate) async fn read(&mut self) -> io::Result<(Correlation, raw::Message)> {
+        loop {
+            if let Some(frame) = self.next_frame() {
+                return frame;
+            }
+            if self.reader.read_buf(&mut self.buf).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+    }
+}
+
+enum Outgoing {
+    Frame(Vec<u8>),
+    Close,
+}
+
+type Response<R> = Result<R, crate::Error<R>>;
+
+/// Requests waiting for their response. `None` once the connection is closed.
+type Pending<R> = Arc<Mutex<Option<HashMap<u32, oneshot::Sender<Response<R>>>>>>;
+
+fn closed_error() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "session closed")
+}
+
+fn heartbeat_frame(correlation: Correlation) -> Outgoing {
+    Outgoing::Frame(encode(
+        correlation,
+        raw::Message {
+            type_marker: 0,
+            body: vec![],
+        },
+    ))
+}
+
+/// Sends messages of type `S` and requests answered by messages of type `R`.
+///
+/// Sending never blocks, the messages get written in order by a background
+/// task. All clones share the same connection.
+pub struct Sender<S, R: raw::TryFromMessage> {
+    frames: mpsc::UnboundedSender<Outgoing>,
+    pending: Pending<R>,
+    next_id: Arc<atomic::AtomicU32>,
+    writer: Arc<Mutex<Option<task::JoinHandle<io::Result<()>>>>>,
+    monitor: SharedMonitor,
+    _message: PhantomData<fn(S)>,
+}
+
+impl<S, R: raw::TryFromMessage> Clone for Sender<S, R> {
+    fn clone(&self) -> Self {
+        Sender {
+            frames: self.frames.clone(),
+            pending: self.pending.clone(),
+            next_id: self.next_id.clone(),
+            writer: self.writer.clone(),
+            monitor: self.monitor.clone(),
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<S, R> Sender<S, R>
+where
+    S: raw::IntoMessage,
+    R: raw::TryFromMessage,
+{
+    fn write(&self, correlation: Correlation, message: S) -> Result<(), crate::Error<R>> {
+        self.frames
+            .send(Outgoing::Frame(encode(
+                correlation,
+                message.into_raw_message(),
+            )))
+            .map_err(|_| closed_error().into())
+    }
+
+    /// Sends a message that expects no response
+    pub fn send(&self, message: S) -> Result<(), crate::Error<R>> {
+        self.write(Correlation::None, message)
+    }
+
+    /// Answers a received request
+    pub fn respond(&self, id: RequestId, message: S) -> Result<(), crate::Error<R>> {
+        self.write(Correlation::Response(id.0), message)
+    }
+
+    /// Sends a request and waits for its response. This is cancel safe, a
+    /// response to a dropped request gets discarded.
+    pub async fn request(&self, message: S) -> Result<R, crate::Error<R>> {
+        let id = self.next_id.fetch_add(1, atomic::Ordering::Relaxed);
+        let (response_sender, response) = oneshot::channel();
+        self.pending
+            .lock()
+            .expect("pending requests are never poisoned")
+            .as_mut()
+            .ok_or_else(closed_error)?
+            .insert(id, response_sender);
+
+        self.write(Correlation::Request(id), message)?;
+        response.await.map_err(|_| closed_error())?
+    }
+
+    /// Health of the connection, as measured by the heartbeat
+    pub fn health(&self) -> Health {
+        self.monitor
+            .lock()
+            .expect("monitor is never poisoned")
+            .health
+    }
+
+    /// Writes the remaining messages and closes the connection
+    pub async fn close(&self) -> Result<(), crate::Error<R>> {
+        let _ = self.frames.send(Outgoing::Close);
+        let writer = self
+            .writer
+            .lock()
+            .expect("writer handle is never poisoned")
+            .take();
+        if let Some(writer) = writer {
+            writer.await.map_err(io::Error::other)??;
+        }
+        Ok(())
+    }
+}
+
+/// Receives the messages and requests of type `R` sent by the other side.
+pub struct Receiver<R: raw::TryFromMessage> {
+    incoming: mpsc::UnboundedReceiver<Result<Incoming<R>, crate::Error<R>>>,
+}
+
+impl<R: raw::TryFromMessage> Receiver<R> {
+    /// Waits for the next message or request. This is cancel safe.
+    pub async fn recv(&mut self) -> Result<Incoming<R>, crate::Error<R>> {
+        match self.incoming.recv().await {
+            Some(incoming) => incoming,
+            None => Err(closed_error().into()),
+        }
+    }
+}
+
+async fn write_frames<W>(
+    mut writer: W,
+    mut frames: mpsc::UnboundedReceiver<Outgoing>,
+) -> io::Result<()>
+where
+    W: io::AsyncWrite + std::marker::Unpin,
+{
+    while let Some(Outgoing::Frame(frame)) = frames.recv().await {
+        writer.write_all(&frame).await?;
+        writer.flush().await?;
+    }
+    writer.shutdown().await
+}
+
+async fn read_frames<Rd, R>(
+    mut reader: FrameReader<Rd>,
+    pending: Pending<R>,
+    incoming: mpsc::UnboundedSender<Result<Incoming<R>, crate::Error<R>>>,
+    frames: mpsc::WeakUnboundedSender<Outgoing>,
+    monitor: SharedMonitor,
+) where
+    Rd: io::AsyncRead + std::marker::Unpin,
+    R: raw::TryFromMessage,
+{
+    loop {
+        let (correlation, message) = match reader.read().await {
+            Ok(frame) => frame,
+            Err(err) => {
+                let _ = incoming.send(Err(err.into()));
+                break;
+            }
+        };
+        let now = time::Instant::now();
+        monitor
+            .lock()
+            .expect("monitor is never poisoned")
+            .health
+            .last_seen = now;
+
+        match correlation {
+            Correlation::Ping(id) => {
+                if let Some(frames) = frames.upgrade() {
+                    let _ = frames.send(heartbeat_frame(Correlation::Pong(id)));
+                }
+                continue;
+            }
+            Correlation::Pong(id) => {
+                let mut monitor = monitor.lock().expect("monitor is never poisoned");
+                if let Some((ping, sent)) = monitor.ping {
+                    if ping == id {
+                        monitor.health.latency = Some(now - sent);
+                        monitor.ping = None;
+                    }
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let message = R::try_from_raw_message(message).map_err(crate::Error::from_prot_err);
+        match correlation {
+            Correlation::Response(id) => {
+                let response_sender = pending
+                    .lock()
+                    .expect("pending requests are never poisoned")
+                    .as_mut()
+                    .and_then(|pending| pending.remove(&id));
+                // the request may have been dropped in the meantime
+                if let Some(response_sender) = response_sender {
+                    let _ = response_sender.send(message);
+                }
+            }
+            Correlation::Request(id) => {
+                let _ = incoming.send(message.map(|m| Incoming::Request(RequestId(id), m)));
+            }
+            Correlation::None => {
+                let _ = incoming.send(message.map(Incoming::Message));
+            }
+            Correlation::Ping(_) | Correlation::Pong(_) => unreachable!("handled above"),
+        }
+    }
+
+    // waiting requests fail, as their responses will never arrive
+    pending
+        .lock()
+        .expect("pending requests are never poisoned")
+        .take();
+}
+
+/// Pings the other side regularly, until the reader stops. If the other side
+/// stays silent for too long, the reader gets stopped and the connection
+/// counts as dead.
+async fn beat<R>(
+    heartbeat: Heartbeat,
+    mut reader: task::JoinHandle<()>,
+    pending: Pending<R>,
+    incoming: mpsc::UnboundedSender<Result<Incoming<R>, crate::Error<R>>>,
+    frames: mpsc::WeakUnboundedSender<Outgoing>,
+    monitor: SharedMonitor,
+) where
+    R: raw::TryFromMessage,
+{
+    let mut interval = time::interval(heartbeat.interval);
+    let mut next_ping = 0;
+    loop {
+        tokio::select! {
+            // the connection got closed, the reader has cleaned up
+            _ = &mut reader => break,
+            _ = interval.tick() => {}
+        }
+
+        let now = time::Instant::now();
+        {
+            let mut monitor = monitor.lock().expect("monitor is never poisoned");
+            if now - monitor.health.last_seen >= heartbeat.timeout {
+                break;
+            }
+            monitor.ping = Some((next_ping, now));
+        }
+        // every sender got dropped, so the writer closes the connection
+        let Some(frames) = frames.upgrade() else {
+            reader.abort();
+            return;
+        };
+        let _ = frames.send(heartbeat_frame(Correlation::Ping(next_ping)));
+        next_ping = next_ping.wrapping_add(1);
+    }
+
+    if !reader.is_finished() {
+        reader.abort();
+        pending
+            .lock()
+            .expect("pending requests are never poisoned")
+            .take();
+        let _ = incoming.send(Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "the other side stopped answering",
+        )
+        .into()));
+        if let Some(frames) = frames.upgrade() {
+            let _ = frames.send(Outgoing::Close);
+        }
+    }
+}
+
+/// Starts a session on the connection. Both sides can send messages and
+/// requests at any time, which get matched with their responses by id.
+pub fn start<S, R, T>(stream: T, heartbeat: Heartbeat) -> (Sender<S, R>, Receiver<R>)
+where
+    S: raw::IntoMessage,
+    R: raw::TryFromMessage + Send + 'static,
+    R::Error: Send,
+    T: io::AsyncRead + io::AsyncWrite + Send + 'static,
+{
+    let (reader, writer) = io::split(stream);
+    let (frames, frames_receiver) = mpsc::unbounded_channel();
+    let (incoming_sender, incoming) = mpsc::unbounded_channel();
+    let pending: Pending<R> = Arc::new(Mutex::new(Some(HashMap::new())));
+    let monitor = Arc::new(Mutex::new(Monitor {
+        health: Health {
+            latency: None,
+            last_seen: time::Instant::now(),
+        },
+        ping: None,
+    }));
+
+    let writer = tokio::spawn(write_frames(writer, frames_receiver));
+    let reader = tokio::spawn(read_frames(
+        FrameReader::new(reader),
+        pending.clone(),
+        incoming_sender.clone(),
+        frames.downgrade(),
+        monitor.clone(),
+    ));
+    tokio::spawn(beat(
+        heartbeat,
+        reader,
+        pending.clone(),
+        incoming_sender,
+        frames.downgrade(),
+        monitor.clone(),
+    ));
+
+    (
+        Sender {
+            frames,
+            pending,
+            next_id: Arc::new(atomic::AtomicU32::new(1)),
+            writer: Arc::new(Mutex::new(Some(writer))),
+            monitor,
+            _message: PhantomData,
+        },
+        Receiver { incoming },
+    )
+}
@@ -0,0 +1,86 @@
+use std::{sync::Mutex, time};
+
+use crate::player::Player;
+
+/// Name the computer player is known by, no client may log in with it
+pub const NAME: &str = "computer";
+
+/// When and how the server plays against a waiting player itself
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// How long a player waits for an opponent, before playing the computer
+    pub wait: time::Duration,
+    /// One of [`ai::STRATEGIES`]
+    pub strategy: String,
+}
+
+/// A player deciding in-process with a strategy
+pub struct Computer {
+    strategy: Mutex<Box<dyn ai::Strategy + Send>>,
+}
+
+impl Computer {
+    pub fn new(strategy: Box<dyn ai::Strategy + Send>) -> Computer {
+        Computer {
+            strategy: Mutex::new(strategy),
+        }
+    }
+
+    /// A built-in strategy, see [`ai::by_name`]
+    pub fn by_name(name: &str, seed: u64) -> Result<Computer, crate::stream::Error> {
+        ai::by_name(name, seed)
+            .map(Computer::new)
+            .ok_or_else(|| crate::stream::Error::UnknownStrategy(name.to_string()))
+    }
+
+    /// The configured strategy, seeded with the current time
+    pub fn from_config(config: &Config) -> Result<Computer, crate::stream::Error> {
+        let seed = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Computer::by_name(&config.strategy, seed)
+    }
+
+    fn strategy(&self) -> std::sync::MutexGuard<'_, Box<dyn ai::Strategy + Send>> {
+        self.strategy.lock().expect("strategy is never poisoned")
+    }
+}
+
+impl Player for Computer {
+    async fn request_board(&self) -> Result<logic::Ships, crate::stream::Error> {
+        Ok(self.strategy().place_ships())
+    }
+
+    async fn request_target(&self) -> Result<logic::Position, crate::stream::Error> {
+        Ok(self.strategy().choose_target())
+    }
+
+    async fn request_inform_target_selection(&self) -> Result<(), crate::stream::Error> {
+        Ok(())
+    }
+
+    async fn request_inform_attack_info_client(
+        &self,
+        _: logic::board::AttackInfo,
+        _: logic::Position,
+    ) -> Result<(), crate::stream::Error> {
+        Ok(())
+    }
+
+    async fn request_inform_attack_info_opponent(
+        &self,
+        attack_info: logic::board::AttackInfo,
+        pos: logic::Position,
+    ) -> Result<(), crate::stream::Error> {
+        self.strategy().observe(pos, attack_info);
+        Ok(())
+    }
+
+    async fn request_inform_victory(&self) -> Result<(), crate::stream::Error> {
+        Ok(())
+    }
+
+    async fn request_inform_loss(&self) -> Result<(), crate::stream::Error> {
+        Ok(())
+    }
+}
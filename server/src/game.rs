@@ -1,94 +1,332 @@
-pub struct Game {
-    stream1: crate::Stream,
-    stream2: crate::Stream,
+pub use logic::game::Player;
 
-    board1: logic::Board,
-    board2: logic::Board,
-    turn: u8,
+/// A single shot of a match
+#[derive(Debug, Clone, Copy)]
+pub struct Move {
+    pub player: Player,
+    pub target: logic::Position,
+    pub attack_info: logic::board::AttackInfo,
 }
 
-impl Game {
+/// How a finished match went. Values indexed by player follow [`Player::index`].
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub winner: Player,
+    pub surrendered: bool,
+    pub shots: [u16; 2],
+    pub hits: [u16; 2],
+    pub ships: [logic::Ships; 2],
+    /// Every shot, in the order they were fired
+    pub moves: Vec<Move>,
+}
+
+/// A single match between two [`crate::player::Player`]s, which may be of
+/// different kinds. The rules are left to [`logic::Game`].
+pub struct Game<'p, A, B> {
+    player1: &'p A,
+    player2: &'p B,
+    rules: logic::Game,
+
+    ships: [logic::Ships; 2],
+    shots: [u16; 2],
+    hits: [u16; 2],
+    moves: Vec<Move>,
+}
+
+impl<'p, A: crate::player::Player, B: crate::player::Player> Game<'p, A, B> {
+    /// Requests the boards of both players. `first` is the player who shoots first.
     pub async fn new(
-        mut stream1: crate::Stream,
-        mut stream2: crate::Stream,
-    ) -> Result<Game, crate::stream::Error> {
+        player1: &'p A,
+        player2: &'p B,
+        first: Player,
+    ) -> Result<Game<'p, A, B>, crate::stream::Error> {
+        let (ships1, ships2) = tokio::join!(player1.request_board(), player2.request_board());
+        let ships = [ships1?, ships2?];
+        let mut rules = logic::Game::new(first);
+        rules.place(Player::One, ships[0])?;
+        rules.place(Player::Two, ships[1])?;
         Ok(Game {
-            board1: logic::Board::from_ships(stream1.request_board().await?),
-            board2: logic::Board::from_ships(stream2.request_board().await?),
-            stream1,
-            stream2,
-            turn: 0,
+            player1,
+            player2,
+            rules,
+            ships,
+            shots: [0, 0],
+            hits: [0, 0],
+            moves: vec![],
         })
     }
 
-    pub fn split_player_streams(&mut self) -> (&mut crate::Stream, &mut crate::Stream) {
-        if self.turn % 2 == 0 {
-            (&mut self.stream1, &mut self.stream2)
-        } else {
-            (&mut self.stream2, &mut self.stream1)
-        }
-    }
+    /// Lets the current player shoot. The winner, once the match is over.
+    async fn play_turn(&mut self) -> Result<Option<Player>, crate::stream::Error> {
+        let logic::game::Phase::Turn(shooter) = self.rules.phase() else {
+            unreachable!("turns are only played until the match is over");
+        };
+        let (target, events) = match shooter {
+            Player::One => shoot(self.player1, self.player2, &mut self.rules, shooter).await?,
+            Player::Two => shoot(self.player2, self.player1, &mut self.rules, shooter).await?,
+        };
 
-    pub fn split_player_boards(&mut self) -> (&mut logic::Board, &mut logic::Board) {
-        if self.turn % 2 == 0 {
-            (&mut self.board1, &mut self.board2)
-        } else {
-            (&mut self.board2, &mut self.board1)
+        let mut winner = None;
+        for event in events {
+            match event {
+                logic::game::Event::Victory { winner: player, .. } => winner = Some(player),
+                event => {
+                    let attack_info = event.attack_info().expect("shots have a result");
+                    self.shots[shooter.index()] += 1;
+                    if let logic::board::AttackInfo::Hit(_) = attack_info {
+                        self.hits[shooter.index()] += 1;
+                    }
+                    self.moves.push(Move {
+                        player: shooter,
+                        target,
+                        attack_info,
+                    });
+                }
+            }
+        }
+        if let Some(winner) = winner {
+            self.inform_outcome(winner).await?;
         }
+        Ok(winner)
     }
 
-    async fn play_turn(&mut self) -> Result<bool, crate::stream::Error> {
-        let (player, opponent) = self.split_player_streams();
-        let (target, success) = tokio::join!(
-            player.request_target(),
-            opponent.request_inform_target_selection(),
-        );
-        let target = target?;
-        success?;
-
-        let (_, opponent_board) = self.split_player_boards();
-        let attack_info = opponent_board.target(target)?;
-
-        let (player, opponent) = self.split_player_streams();
-        let (success1, success2) = tokio::join!(
-            player.request_inform_attack_info_opponent(attack_info, target),
-            opponent.request_inform_attack_info_client(attack_info, target),
-        );
+    async fn inform_outcome(&self, winner: Player) -> Result<(), crate::stream::Error> {
+        let (success1, success2) = match winner {
+            Player::One => tokio::join!(
+                self.player1.request_inform_victory(),
+                self.player2.request_inform_loss(),
+            ),
+            Player::Two => tokio::join!(
+                self.player1.request_inform_loss(),
+                self.player2.request_inform_victory(),
+            ),
+        };
         success1?;
         success2?;
+        Ok(())
+    }
 
-        match attack_info {
-            logic::board::AttackInfo::Hit(None) => Ok(true),
-            logic::board::AttackInfo::Miss => {
-                self.turn += 1;
-                Ok(true)
-            }
+    /// Ends the match in favor of the player who did not surrender
+    async fn surrender(&mut self, loser: Player) -> Result<Outcome, crate::stream::Error> {
+        self.rules.surrender(loser)?;
+        self.inform_outcome(loser.other()).await?;
+        Ok(self.outcome(loser.other(), true))
+    }
+
+    fn outcome(&mut self, winner: Player, surrendered: bool) -> Outcome {
+        Outcome {
+            winner,
+            surrendered,
+            shots: self.shots,
+            hits: self.hits,
+            ships: self.ships,
+            moves: std::mem::take(&mut self.moves),
+        }
+    }
 
-            logic::board::AttackInfo::Hit(Some(_)) => {
-                let (_, opponent_board) = self.split_player_boards();
-                if opponent_board.is_all_sunken() {
-                    let (player, opponent) = self.split_player_streams();
-                    let (success1, success2) = tokio::join!(
-                        player.request_inform_victory(),
-                        opponent.request_inform_loss(),
-                    );
-                    success1?;
-                    success2?;
-                    Ok(false)
-                } else {
-                    Ok(true)
+    /// Plays the match until one player has lost all ships or surrendered.
+    /// A surrender interrupts the current turn.
+    pub(crate) async fn play(
+        mut self,
+        relay: &mut crate::relay::Relay,
+    ) -> Result<Outcome, crate::stream::Error> {
+        loop {
+            tokio::select! {
+                winner = self.play_turn() => {
+                    if let Some(winner) = winner? {
+                        break Ok(self.outcome(winner, false));
+                    }
                 }
+                Some(loser) = relay.surrender() => break self.surrender(loser).await,
             }
         }
     }
 
-    pub async fn play(mut self) -> Result<(), crate::stream::Error> {
+    /// Plays the match until one player has lost all ships, for players
+    /// without a way to surrender
+    pub async fn play_out(mut self) -> Result<Outcome, crate::stream::Error> {
         loop {
-            match self.play_turn().await {
-                Ok(true) => continue,
-                Ok(false) => break Ok(()),
-                Err(err) => break Err(err),
+            if let Some(winner) = self.play_turn().await? {
+                return Ok(self.outcome(winner, false));
+            }
+        }
+    }
+}
+
+/// Lets `shooter` pick a target on the board of `opponent`, and tells both
+/// what it hit
+async fn shoot(
+    shooter: &impl crate::player::Player,
+    opponent: &impl crate::player::Player,
+    rules: &mut logic::Game,
+    turn: Player,
+) -> Result<(logic::Position, Vec<logic::game::Event>), crate::stream::Error> {
+    let (target, success) = tokio::join!(
+        shooter.request_target(),
+        opponent.request_inform_target_selection(),
+    );
+    success?;
+    let target = target?;
+    let events = rules.fire(turn, target)?;
+    let attack_info = events[0]
+        .attack_info()
+        .expect("a shot is answered before the match ends");
+
+    let (success1, success2) = tokio::join!(
+        shooter.request_inform_attack_info_opponent(attack_info, target),
+        opponent.request_inform_attack_info_client(attack_info, target),
+    );
+    success1?;
+    success2?;
+    Ok((target, events))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{computer::Computer, player};
+
+    /// Ships in the even rows, starting at the left edge
+    fn fleet() -> logic::Ships {
+        let ships = [5, 4, 3, 3, 2]
+            .into_iter()
+            .enumerate()
+            .map(|(row, length)| {
+                logic::ship::Ship::try_from(logic::ship::ShipPlan::Horizontal {
+                    pos: logic::Position::try_from_coords((0, row as u8 * 2)).unwrap(),
+                    length,
+                })
+                .unwrap()
+            });
+        logic::Ships::try_from(<[_; 5]>::try_from(ships.collect::<Vec<_>>()).unwrap()).unwrap()
+    }
+
+    /// Shoots row by row, moving `step` positions every shot, and writes
+    /// down what it gets told
+    struct Sweeper {
+        step: u8,
+        next: Mutex<u8>,
+        told: Mutex<Vec<String>>,
+    }
+
+    impl Sweeper {
+        fn new(step: u8) -> Sweeper {
+            Sweeper {
+                step,
+                next: Mutex::new(0),
+                told: Mutex::new(vec![]),
             }
         }
+
+        fn tell(&self, text: String) -> Result<(), crate::stream::Error> {
+            self.told.lock().unwrap().push(text);
+            Ok(())
+        }
+    }
+
+    impl player::Player for Sweeper {
+        async fn request_board(&self) -> Result<logic::Ships, crate::stream::Error> {
+            Ok(fleet())
+        }
+
+        async fn request_target(&self) -> Result<logic::Position, crate::stream::Error> {
+            let mut next = self.next.lock().unwrap();
+            let target = logic::Position::try_from_coords((*next % 10, *next / 10)).unwrap();
+            *next += self.step;
+            Ok(target)
+        }
+
+        async fn request_inform_target_selection(&self) -> Result<(), crate::stream::Error> {
+            self.tell("selection".to_string())
+        }
+
+        async fn request_inform_attack_info_client(
+            &self,
+            attack_info: logic::board::AttackInfo,
+            pos: logic::Position,
+        ) -> Result<(), crate::stream::Error> {
+            self.tell(format!("client {pos} {attack_info:?}"))
+        }
+
+        async fn request_inform_attack_info_opponent(
+            &self,
+            attack_info: logic::board::AttackInfo,
+            pos: logic::Position,
+        ) -> Result<(), crate::stream::Error> {
+            self.tell(format!("opponent {pos} {attack_info:?}"))
+        }
+
+        async fn request_inform_victory(&self) -> Result<(), crate::stream::Error> {
+            self.tell("victory".to_string())
+        }
+
+        async fn request_inform_loss(&self) -> Result<(), crate::stream::Error> {
+            self.tell("loss".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn hits_shoot_again() {
+        let (sweeper1, sweeper2) = (Sweeper::new(1), Sweeper::new(1));
+        let game = Game::new(&sweeper1, &sweeper2, Player::Two).await.unwrap();
+        let outcome = game.play_out().await.unwrap();
+
+        // both shoot the same way, so the one shooting first wins
+        assert_eq!(outcome.winner, Player::Two);
+        assert!(!outcome.surrendered);
+        assert_eq!(outcome.hits[1], 17);
+        assert!(outcome.hits[0] < 17);
+        assert_eq!(
+            outcome.shots.iter().sum::<u16>() as usize,
+            outcome.moves.len()
+        );
+        assert_eq!(outcome.moves[0].player, Player::Two);
+        for shots in outcome.moves.windows(2) {
+            let shooter = match shots[0].attack_info {
+                logic::board::AttackInfo::Miss => shots[0].player.other(),
+                logic::board::AttackInfo::Hit(_) => shots[0].player,
+            };
+            assert_eq!(shots[1].player, shooter);
+        }
+
+        let (told1, told2) = (sweeper1.told.lock().unwrap(), sweeper2.told.lock().unwrap());
+        assert_eq!(told1.last().unwrap(), "loss");
+        assert_eq!(told2.last().unwrap(), "victory");
+        assert_eq!(
+            told2[..2],
+            ["opponent A1 Hit(None)", "opponent B1 Hit(None)"]
+        );
+        assert_eq!(told1[..2], ["selection", "client A1 Hit(None)"]);
+        let selections = told1.iter().filter(|text| *text == "selection").count();
+        assert_eq!(selections, outcome.shots[1] as usize);
+    }
+
+    #[tokio::test]
+    async fn same_target_twice_is_rejected() {
+        let (stubborn, sweeper) = (Sweeper::new(0), Sweeper::new(1));
+        let game = Game::new(&stubborn, &sweeper, Player::One).await.unwrap();
+        assert!(matches!(
+            game.play_out().await,
+            Err(crate::stream::Error::ClientResponseTargetAlreadyHit(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn computers_play_deterministically() {
+        let play = || async {
+            let hunt = Computer::by_name("hunt", 1).unwrap();
+            let random = Computer::by_name("random", 2).unwrap();
+            let game = Game::new(&hunt, &random, Player::One).await.unwrap();
+            game.play_out().await.unwrap()
+        };
+        let (outcome1, outcome2) = (play().await, play().await);
+
+        assert_eq!(outcome1.winner, Player::One);
+        assert_eq!(outcome1.hits[0], 17);
+        let targets =
+            |outcome: &Outcome| outcome.moves.iter().map(|m| m.target).collect::<Vec<_>>();
+        assert_eq!(targets(&outcome1), targets(&outcome2));
     }
 }
@@ -0,0 +1,301 @@
+use std::{collections::HashMap, path, time};
+
+use tokio::{fs, io::AsyncWriteExt};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("server :: history :: io :: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// How a match ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum End {
+    /// All ships of the loser were sunk
+    Sunk,
+    /// The loser surrendered
+    Surrender,
+}
+
+/// A finished match, as stored in the history. Values indexed by player
+/// follow the order of [`MatchRecord::players`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchRecord {
+    pub players: [String; 2],
+    /// When the match started, in seconds since the unix epoch
+    pub started: u64,
+    pub duration: time::Duration,
+    /// Number of matches the series was played over
+    pub best_of: Option<u8>,
+    /// Index of the player who shot first
+    pub first: usize,
+    pub shots: [u16; 2],
+    pub hits: [u16; 2],
+    /// Index of the player who won
+    pub winner: usize,
+    pub end: End,
+}
+
+impl MatchRecord {
+    /// Hits per shot of the player, `None` if they did not shoot
+    pub fn accuracy(&self, player: usize) -> Option<f64> {
+        (self.shots[player] > 0).then(|| self.hits[player] as f64 / self.shots[player] as f64)
+    }
+
+    /// One line, the fields separated by tabs
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            self.started,
+            self.duration.as_millis(),
+            self.players[0],
+            self.players[1],
+            self.best_of.unwrap_or(0),
+            self.first,
+            self.shots[0],
+            self.hits[0],
+            self.shots[1],
+            self.hits[1],
+            self.winner,
+            match self.end {
+                End::Sunk => "sunk",
+                End::Surrender => "surrender",
+            },
+        )
+    }
+
+    fn from_line(line: &str) -> Option<MatchRecord> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [started, duration, player1, player2, best_of, first, shots1, hits1, shots2, hits2, winner, end] =
+            fields[..]
+        else {
+            return None;
+        };
+        let player = |index: &str| index.parse().ok().filter(|&i: &usize| i < 2);
+
+        Some(MatchRecord {
+            players: [player1.to_string(), player2.to_string()],
+            started: started.parse().ok()?,
+            duration: time::Duration::from_millis(duration.parse().ok()?),
+            best_of: Some(best_of.parse().ok()?).filter(|&best_of| best_of != 0),
+            first: player(first)?,
+            shots: [shots1.parse().ok()?, shots2.parse().ok()?],
+            hits: [hits1.parse().ok()?, hits2.parse().ok()?],
+            winner: player(winner)?,
+            end: match end {
+                "sunk" => End::Sunk,
+                "surrender" => End::Surrender,
+                _ => return None,
+            },
+        })
+    }
+}
+
+/// Appends the match to the history file, creating it if needed
+pub async fn append(path: &path::Path, record: &MatchRecord) -> Result<(), Error> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    // a single write, so records of concurrent series do not interleave
+    file.write_all(record.to_line().as_bytes()).await?;
+    Ok(())
+}
+
+/// Reads all matches of the history file, oldest first. A missing file is an
+/// empty history. Invalid lines, like one left half written by a crash, are
+/// skipped with a warning.
+pub fn load(path: &path::Path) -> Result<Vec<MatchRecord>, Error> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .filter_map(|(i, line)| {
+            let record = MatchRecord::from_line(line);
+            if record.is_none() {
+                tracing::warn!("{}:{} :: skipped invalid record", path.display(), i + 1);
+            }
+            record
+        })
+        .collect())
+}
+
+/// Statistics of one player over all recorded matches
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerStats {
+    pub name: String,
+    pub matches: u32,
+    pub wins: u32,
+    pub shots: u32,
+    pub hits: u32,
+    /// Shots fired in matches won by sinking all ships
+    shots_in_wins: u32,
+    sunk_wins: u32,
+    /// Wins in a row if positive, losses in a row if negative
+    pub streak: i32,
+    pub longest_win_streak: u32,
+}
+
+impl PlayerStats {
+    pub fn win_rate(&self) -> f64 {
+        self.wins as f64 / self.matches as f64
+    }
+
+    pub fn accuracy(&self) -> Option<f64> {
+        (self.shots > 0).then(|| self.hits as f64 / self.shots as f64)
+    }
+
+    /// Average shots needed to sink all ships of the opponent
+    pub fn average_shots_to_win(&self) -> Option<f64> {
+        (self.sunk_wins > 0).then(|| self.shots_in_wins as f64 / self.sunk_wins as f64)
+    }
+
+    fn add(&mut self, record: &MatchRecord, player: usize) {
+        self.matches += 1;
+        self.shots += record.shots[player] as u32;
+        self.hits += record.hits[player] as u32;
+
+        if record.winner == player {
+            self.wins += 1;
+            if record.end == End::Sunk {
+                self.sunk_wins += 1;
+                self.shots_in_wins += record.shots[player] as u32;
+            }
+            self.streak = self.streak.max(0) + 1;
+            self.longest_win_streak = self.longest_win_streak.max(self.streak as u32);
+        } else {
+            self.streak = self.streak.min(0) - 1;
+        }
+    }
+}
+
+/// Statistics of every player in the history, ordered by win rate
+pub fn stats(records: &[MatchRecord]) -> Vec<PlayerStats> {
+    let mut players: HashMap<&str, PlayerStats> = HashMap::new();
+    for record in records {
+        for (player, name) in record.players.iter().enumerate() {
+            players
+                .entry(name)
+                .or_insert_with(|| PlayerStats {
+                    name: name.clone(),
+                    ..Default::default()
+                })
+                .add(record, player);
+        }
+    }
+
+    let mut stats: Vec<PlayerStats> = players.into_values().collect();
+    stats.sort_by(|a, b| {
+        b.win_rate()
+            .total_cmp(&a.win_rate())
+            .then(b.matches.cmp(&a.matches))
+            .then(a.name.cmp(&b.name))
+    });
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(players: [&str; 2], winner: usize, end: End) -> MatchRecord {
+        MatchRecord {
+            players: players.map(String::from),
+            started: 1_700_000_000,
+            duration: time::Duration::from_millis(83_250),
+            best_of: Some(3),
+            first: 1,
+            shots: [40, 52],
+            hits: [17, 15],
+            winner,
+            end,
+        }
+    }
+
+    #[test]
+    fn record_lines() {
+        for record in [
+            record(["ada", "bob"], 0, End::Sunk),
+            MatchRecord {
+                best_of: None,
+                ..record(["ada", "bob"], 1, End::Surrender)
+            },
+        ] {
+            let line = record.to_line();
+            assert!(line.ends_with('\n'));
+            assert_eq!(MatchRecord::from_line(line.trim_end()), Some(record));
+        }
+
+        let line = record(["ada", "bob"], 0, End::Sunk).to_line();
+        let line = line.trim_end();
+        assert_eq!(
+            MatchRecord::from_line(&line.replace("sunk", "drowned")),
+            None
+        );
+        assert_eq!(
+            MatchRecord::from_line(&line.replacen("\t1\t", "\t2\t", 1)),
+            None
+        );
+        assert_eq!(MatchRecord::from_line(&line.replacen("40", "-4", 1)), None);
+        assert_eq!(MatchRecord::from_line(&line[..line.len() / 2]), None);
+        assert_eq!(MatchRecord::from_line(""), None);
+    }
+
+    #[test]
+    fn load_skips_invalid_lines() {
+        let path = std::env::temp_dir().join(format!("ziel-history-{}", std::process::id()));
+        let records = [
+            record(["ada", "bob"], 0, End::Sunk),
+            record(["bob", "cy"], 1, End::Surrender),
+        ];
+        let half_written = &records[0].to_line()[..20];
+        let content = format!(
+            "{}garbage\n\n{}{half_written}",
+            records[0].to_line(),
+            records[1].to_line()
+        );
+        std::fs::write(&path, content).unwrap();
+        let loaded = load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), records);
+        assert_eq!(load(&path).unwrap(), []);
+    }
+
+    #[test]
+    fn player_stats() {
+        let records = [
+            record(["ada", "bob"], 0, End::Sunk),
+            record(["ada", "bob"], 0, End::Surrender),
+            record(["bob", "ada"], 0, End::Sunk),
+            record(["ada", "bob"], 0, End::Sunk),
+            record(["bob", "ada"], 1, End::Sunk),
+        ];
+        let stats = stats(&records);
+        let [ada, bob] = &stats[..] else {
+            panic!("expected two players")
+        };
+
+        assert_eq!((ada.name.as_str(), ada.matches, ada.wins), ("ada", 5, 4));
+        assert_eq!(ada.streak, 2);
+        assert_eq!(ada.longest_win_streak, 2);
+        assert_eq!(bob.streak, -2);
+        assert_eq!(bob.longest_win_streak, 1);
+
+        // wins by surrender do not count, ada shot 40, 40 and 52 times
+        assert_eq!(ada.average_shots_to_win(), Some(44.0));
+        assert_eq!(bob.average_shots_to_win(), Some(40.0));
+        assert_eq!(PlayerStats::default().average_shots_to_win(), None);
+        assert_eq!(
+            ada.accuracy(),
+            Some((17 * 3 + 15 * 2) as f64 / (40 * 3 + 52 * 2) as f64)
+        );
+    }
+}
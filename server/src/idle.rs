@@ -0,0 +1,92 @@
+use tokio::{sync::oneshot, task};
+
+use protocol::{client, session::Incoming};
+
+use crate::{
+    players::{Login, SharedRegistry},
+    stream::Connection,
+};
+
+/// A logged in client without an opponent. A task answers its requests, until
+/// the connection gets taken back with [`Idle::resume`].
+pub struct Idle {
+    stream: crate::Stream,
+    login: Login,
+    /// Stops the task answering the client, which hands the inbox back
+    stop: oneshot::Sender<()>,
+    /// Returns `None` if the client disconnected
+    task: task::JoinHandle<Option<crate::stream::Inbox>>,
+}
+
+impl Idle {
+    pub fn new((stream, inbox, login): Connection, registry: &SharedRegistry) -> Idle {
+        let (stop, stopped) = oneshot::channel();
+        Idle {
+            task: tokio::spawn(answer(stream.clone(), inbox, registry.clone(), stopped)),
+            stream,
+            login,
+            stop,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.login.name()
+    }
+
+    pub fn stream(&self) -> &crate::Stream {
+        &self.stream
+    }
+
+    /// The task answering the client only ends early, if it disconnected
+    pub fn is_connected(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Takes the connection back, `None` if the client disconnected
+    pub async fn resume(self) -> Option<Connection> {
+        let _ = self.stop.send(());
+        let inbox = self.task.await.ok()??;
+        Some((self.stream, inbox, self.login))
+    }
+}
+
+/// Answers the requests of an idle client, until it gets stopped or the
+/// client disconnects
+async fn answer(
+    stream: crate::Stream,
+    mut inbox: crate::stream::Inbox,
+    registry: SharedRegistry,
+    mut stop: oneshot::Receiver<()>,
+) -> Option<crate::stream::Inbox> {
+    loop {
+        let incoming = tokio::select! {
+            _ = &mut stop => return Some(inbox),
+            incoming = inbox.recv() => incoming,
+        };
+        match incoming {
+            Ok(Incoming::Request(id, client::Message::RequestLeaderboard)) => {
+                let standings = registry
+                    .lock()
+                    .expect("registry is never poisoned")
+                    .leaderboard();
+                if let Err(err) = stream.respond_leaderboard(id, standings) {
+                    tracing::warn!("idle :: {err}");
+                    return None;
+                }
+            }
+            // there is no opponent, to chat with or surrender to
+            Ok(Incoming::Message(client::Message::Chat(_) | client::Message::Surrender)) => {}
+            Ok(Incoming::Message(res) | Incoming::Request(_, res)) => {
+                let err = crate::stream::Error::ClientMessageUnexpected(res);
+                tracing::warn!("idle :: {err}");
+            }
+            Err(protocol::Error::Protocol(err)) => {
+                tracing::warn!("idle :: invalid message :: {err}");
+            }
+            Err(err) => {
+                tracing::info!("idle :: connection closed :: {err}");
+                return None;
+            }
+        }
+    }
+}
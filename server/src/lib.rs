@@ -2,45 +2,75 @@ use tokio::net;
 
 pub(crate) mod stream;
 pub(crate) use stream::Stream;
-mod game;
+pub mod computer;
+pub mod game;
+pub mod history;
+mod idle;
+mod matchmaking;
+pub mod player;
+pub mod players;
+mod relay;
+mod replay;
+mod series;
+pub mod tournament;
+
+/// Settings for the matches hosted by the server
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// Number of matches a series is played over. The series ends as soon as
+    /// one player has won the majority. `None` allows rematches until one
+    /// player declines.
+    pub best_of: Option<u8>,
+    /// Let the other player shoot first in every rematch
+    pub swap_first: bool,
+    /// Pings the clients, so dead connections end the series
+    pub heartbeat: protocol::session::Heartbeat,
+    /// File every finished match gets appended to, see [`history`]
+    pub history: Option<std::path::PathBuf>,
+    /// File the names, passwords and ratings of the players are kept in.
+    /// `None` keeps them in memory only.
+    pub players: Option<std::path::PathBuf>,
+    /// Lets the server play against players waiting too long for an
+    /// opponent. `None` lets them wait forever.
+    pub computer: Option<computer::Config>,
+}
 
 async fn handle_connection(
-    stream1: net::TcpStream,
-    stream2: net::TcpStream,
+    stream: net::TcpStream,
+    registry: &players::SharedRegistry,
+    queue: &matchmaking::Queue,
+    config: &Config,
 ) -> Result<(), stream::Error> {
-    let stream1 = Stream::handshake(stream1).await?;
-    let stream2 = Stream::handshake(stream2).await?;
-    tracing::info!("HANDSHAKE successful");
-
-    let game = game::Game::new(stream1, stream2).await?;
-    tracing::info!("board initialization successful");
-
-    tokio::spawn(async move {
-        match game.play().await {
-            Ok(()) => tracing::info!("game thread :: finished successfully"),
-            Err(err) => tracing::error!("game thread :: {err}"),
-        }
-    });
+    let player = Stream::handshake(stream, config.heartbeat, registry).await?;
+    tracing::info!("HANDSHAKE successful :: {}", player.2.name());
+    queue.join(player);
 
     Ok(())
 }
 
-pub async fn listen(addr: std::net::SocketAddr) -> Result<(), stream::Error> {
+pub async fn listen(addr: std::net::SocketAddr, config: Config) -> Result<(), stream::Error> {
+    if let Some(computer) = &config.computer {
+        if !ai::STRATEGIES.contains(&computer.strategy.as_str()) {
+            return Err(stream::Error::UnknownStrategy(computer.strategy.clone()));
+        }
+    }
     let listener = net::TcpListener::bind(addr).await?;
+    let registry = std::sync::Arc::new(std::sync::Mutex::new(
+        players::Registry::load(config.players.clone()).await?,
+    ));
+    let queue = matchmaking::Queue::spawn(registry.clone(), config.clone());
 
     tracing::info!("LISTENING AT [{addr}]");
     loop {
-        let (stream1, addr1) = listener.accept().await?;
-        tracing::info!("ACCEPTED [{addr1}]; waiting for player two");
-        let (stream2, addr2) = listener.accept().await?;
-        tracing::info!("ACCEPTED [{addr2}]; beginning match");
-        tracing::info!("MATCH [{addr1}] vs [{addr2}]");
+        let (stream, addr) = listener.accept().await?;
+        tracing::info!("ACCEPTED [{addr}]");
 
-        let res = handle_connection(stream1, stream2).await;
-
-        match res {
-            Ok(()) => tracing::info!("successfully handled connection"),
-            Err(err) => tracing::error!("error handling connection {err}"),
-        }
+        let (registry, queue, config) = (registry.clone(), queue.clone(), config.clone());
+        tokio::spawn(async move {
+            match handle_connection(stream, &registry, &queue, &config).await {
+                Ok(()) => tracing::info!("[{addr}] waiting for an opponent"),
+                Err(err) => tracing::error!("[{addr}] error handling connection {err}"),
+            }
+        });
     }
 }
@@ -0,0 +1,279 @@
+use tokio::{sync::mpsc, time};
+
+use protocol::server;
+
+use crate::{
+    computer::Computer, idle::Idle, players::SharedRegistry, series::Series, stream::Connection,
+};
+
+/// Rating difference within which two players get matched right away
+const RATING_WINDOW: f64 = 100.0;
+/// How much the window widens for every second the older player waited
+const RATING_WINDOW_GROWTH: f64 = 10.0;
+/// How often the queue tries to pair the waiting players and updates them
+const MATCH_INTERVAL: time::Duration = time::Duration::from_secs(1);
+/// Weight of the latest wait in the average the estimates are based on
+const WAIT_SMOOTHING: f64 = 0.2;
+
+/// Pairs the logged in clients by rating. The longer a player waits, the
+/// larger the rating difference to the opponent may be. Waiting clients get
+/// told their position and estimated wait whenever it changes. Players
+/// waiting too long play against the computer, if configured.
+#[derive(Clone)]
+pub struct Queue {
+    connections: mpsc::UnboundedSender<Connection>,
+}
+
+impl Queue {
+    /// Starts the queue, which begins a [`crate::series::Series`] for every pair
+    pub fn spawn(registry: SharedRegistry, config: crate::Config) -> Queue {
+        let (connections, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run(receiver, registry, config));
+        Queue { connections }
+    }
+
+    pub fn join(&self, connection: Connection) {
+        // the queue only stops together with the runtime
+        let _ = self.connections.send(connection);
+    }
+}
+
+/// A player waiting in the queue
+struct Waiting {
+    idle: Idle,
+    rating: f64,
+    since: time::Instant,
+    /// The status the client was told last
+    status: Option<server::QueueStatus>,
+}
+
+impl Waiting {
+    fn new(connection: Connection, registry: &SharedRegistry, since: time::Instant) -> Waiting {
+        let rating = registry
+            .lock()
+            .expect("registry is never poisoned")
+            .rating(connection.2.name());
+        Waiting {
+            idle: Idle::new(connection, registry),
+            rating,
+            since,
+            status: None,
+        }
+    }
+
+    /// Takes the player out of the queue, returning when they joined. `None`
+    /// if the client disconnected.
+    async fn leave(self) -> Option<(Connection, time::Instant)> {
+        let since = self.since;
+        Some((self.idle.resume().await?, since))
+    }
+}
+
+/// Rating difference a player who joined at `since` accepts
+fn window(since: time::Instant, now: time::Instant) -> f64 {
+    RATING_WINDOW + RATING_WINDOW_GROWTH * (now - since).as_secs_f64()
+}
+
+async fn run(
+    mut connections: mpsc::UnboundedReceiver<Connection>,
+    registry: SharedRegistry,
+    config: crate::Config,
+) {
+    let mut waiting: Vec<Waiting> = vec![];
+    let mut average_wait: Option<f64> = None;
+    let mut interval = time::interval(MATCH_INTERVAL);
+    loop {
+        tokio::select! {
+            connection = connections.recv() => match connection {
+                Some(connection) => {
+                    tracing::info!("QUEUE joined :: {}", connection.2.name());
+                    waiting.push(Waiting::new(connection, &registry, time::Instant::now()));
+                }
+                None => break,
+            },
+            _ = interval.tick() => {}
+        }
+
+        waiting.retain(|player| {
+            let connected = player.idle.is_connected();
+            if !connected {
+                tracing::info!("QUEUE left :: {}", player.idle.name());
+            }
+            connected
+        });
+
+        let now = time::Instant::now();
+        let players: Vec<_> = waiting
+            .iter()
+            .map(|player| (player.rating, player.since))
+            .collect();
+        let mut slots: Vec<_> = waiting.drain(..).map(Some).collect();
+        let pairs: Vec<_> = pairings(&players, now)
+            .into_iter()
+            .map(|(i, j)| {
+                let mut take = |i: usize| slots[i].take().expect("players are paired once");
+                (take(i), take(j))
+            })
+            .collect();
+        waiting = slots.into_iter().flatten().collect();
+
+        let mut record_wait = |player: &Waiting| {
+            let wait = (now - player.since).as_secs_f64();
+            average_wait = Some(match average_wait {
+                Some(average) => average * (1.0 - WAIT_SMOOTHING) + wait * WAIT_SMOOTHING,
+                None => wait,
+            });
+        };
+
+        for (player1, player2) in pairs {
+            record_wait(&player1);
+            record_wait(&player2);
+            match (player1.leave().await, player2.leave().await) {
+                (Some((player1, _)), Some((player2, _))) => {
+                    tracing::info!("MATCH {} vs {}", player1.2.name(), player2.2.name());
+                    let streams = vec![player1.0.clone(), player2.0.clone()];
+                    start_series(Series::new(player1, player2, &config), streams);
+                }
+                // the remaining player keeps their place in the queue
+                (Some((player, since)), None) | (None, Some((player, since))) => {
+                    let position = waiting.partition_point(|other| other.since <= since);
+                    waiting.insert(position, Waiting::new(player, &registry, since));
+                }
+                (None, None) => {}
+            }
+        }
+
+        if let Some(computer) = &config.computer {
+            // the queue is ordered by joining time, so the longest waiting come first
+            let bored = waiting.partition_point(|player| now - player.since >= computer.wait);
+            for player in waiting.drain(..bored).collect::<Vec<_>>() {
+                record_wait(&player);
+                let Some((player, _)) = player.leave().await else {
+                    continue;
+                };
+                match Computer::from_config(computer) {
+                    Ok(opponent) => {
+                        tracing::info!("MATCH {} vs {}", player.2.name(), crate::computer::NAME);
+                        // a failed send shows up in the series
+                        let _ = player.0.inform_chat(format!(
+                            "no opponent was found, you are playing against the computer ({})",
+                            computer.strategy
+                        ));
+                        let streams = vec![player.0.clone()];
+                        start_series(Series::against_computer(player, opponent, &config), streams);
+                    }
+                    Err(err) => tracing::error!("QUEUE computer :: {err}"),
+                }
+            }
+        }
+
+        for (position, player) in waiting.iter_mut().enumerate() {
+            let status = server::QueueStatus {
+                position: (position + 1).try_into().unwrap_or(u16::MAX),
+                estimated_wait: average_wait.map(|average| {
+                    (average - (now - player.since).as_secs_f64()).clamp(0.0, (u16::MAX - 1) as f64)
+                        as u16
+                }),
+            };
+            if player.status != Some(status) {
+                player.status = Some(status);
+                // a failed send shows up as disconnect of the waiting task
+                let _ = player.idle.stream().inform_queue(status);
+            }
+        }
+    }
+}
+
+/// Pairs the rating and joining time of the waiting players, ordered by
+/// joining time. The longest waiting player gets the closest rated player
+/// within their window first. Returns the indices of the pairs, the longer
+/// waiting player first.
+fn pairings(players: &[(f64, time::Instant)], now: time::Instant) -> Vec<(usize, usize)> {
+    let mut paired = vec![false; players.len()];
+    let mut pairs = vec![];
+    for i in 0..players.len() {
+        if paired[i] {
+            continue;
+        }
+        let (rating, since) = players[i];
+        let window = window(since, now);
+        let closest = (i + 1..players.len())
+            .filter(|&j| !paired[j])
+            .map(|j| (j, (players[j].0 - rating).abs()))
+            .filter(|&(_, difference)| difference <= window)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((j, _)) = closest {
+            paired[i] = true;
+            paired[j] = true;
+            pairs.push((i, j));
+        }
+    }
+    pairs
+}
+
+/// Plays the series in the background, closing the `streams` of the clients
+/// once it is over
+fn start_series<P>(series: Series<P>, streams: Vec<crate::Stream>)
+where
+    P: crate::player::Player + Send + 'static,
+{
+    tokio::spawn(async move {
+        match series.play().await {
+            Ok(()) => tracing::info!("game thread :: finished successfully"),
+            Err(err) => tracing::error!("game thread :: {err}"),
+        }
+        // a series ending in an error would leave the other client waiting
+        for stream in streams {
+            let _ = stream.close().await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_grows() {
+        let since = time::Instant::now();
+        assert_eq!(window(since, since), RATING_WINDOW);
+        assert_eq!(
+            window(since, since + time::Duration::from_secs(5)),
+            RATING_WINDOW + 5.0 * RATING_WINDOW_GROWTH
+        );
+    }
+
+    #[test]
+    fn closest_rating_paired() {
+        let now = time::Instant::now();
+        let players = [(1500.0, now), (1580.0, now), (1520.0, now), (1590.0, now)];
+        assert_eq!(pairings(&players, now), [(0, 2), (1, 3)]);
+        assert_eq!(pairings(&players[..1], now), []);
+    }
+
+    #[test]
+    fn window_limits_pairs() {
+        let since = time::Instant::now();
+        let players = [(1500.0, since), (1500.0 + 2.0 * RATING_WINDOW, since)];
+        assert_eq!(pairings(&players, since), []);
+
+        // the wait needed to widen the window to the difference
+        let wait = time::Duration::from_secs_f64(RATING_WINDOW / RATING_WINDOW_GROWTH);
+        assert_eq!(
+            pairings(&players, since + wait - time::Duration::from_secs(1)),
+            []
+        );
+        assert_eq!(pairings(&players, since + wait), [(0, 1)]);
+    }
+
+    #[test]
+    fn longest_waiting_first() {
+        let now = time::Instant::now();
+        let players = [
+            (1500.0, now - time::Duration::from_secs(10)),
+            (1600.0, now),
+            (1610.0, now),
+        ];
+        assert_eq!(pairings(&players, now), [(0, 1)]);
+    }
+}
@@ -0,0 +1,47 @@
+use std::future::Future;
+
+use crate::stream::Error;
+
+/// One side of a [`crate::game::Game`] and a [`crate::series::Series`]. A
+/// [`crate::Stream`] asks the client over the network, a
+/// [`crate::computer::Computer`] decides in-process.
+///
+/// Every request is answered on its own, the game takes care of the rules.
+pub trait Player: Sync {
+    /// The fleet for a new match
+    fn request_board(&self) -> impl Future<Output = Result<logic::Ships, Error>> + Send;
+    /// The next position to shoot at
+    fn request_target(&self) -> impl Future<Output = Result<logic::Position, Error>> + Send;
+    /// The opponent is choosing a target
+    fn request_inform_target_selection(&self) -> impl Future<Output = Result<(), Error>> + Send;
+    /// The opponent shot at `pos`, with the given result
+    fn request_inform_attack_info_client(
+        &self,
+        attack_info: logic::board::AttackInfo,
+        pos: logic::Position,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+    /// The shot at `pos` had the given result
+    fn request_inform_attack_info_opponent(
+        &self,
+        attack_info: logic::board::AttackInfo,
+        pos: logic::Position,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+    fn request_inform_victory(&self) -> impl Future<Output = Result<(), Error>> + Send;
+    fn request_inform_loss(&self) -> impl Future<Output = Result<(), Error>> + Send;
+    /// The score of the series, after every match
+    fn request_inform_score(
+        &self,
+        _score: protocol::server::Score,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        async { Ok(()) }
+    }
+    /// Whether to play another match of the series. By default the decision
+    /// is left to the opponent.
+    fn request_rematch(&self) -> impl Future<Output = Result<bool, Error>> + Send {
+        async { Ok(true) }
+    }
+    /// The series is over
+    fn request_terminate(&self) -> impl Future<Output = Result<(), Error>> + Send {
+        async { Ok(()) }
+    }
+}
@@ -0,0 +1,552 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path,
+    sync::{Arc, Mutex},
+};
+
+use protocol::{client, server};
+use sha2::Sha256;
+use tokio::fs;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("server :: players :: io :: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("server :: players :: line {0} :: invalid entry")]
+    InvalidEntry(usize),
+}
+
+/// Rating of players without any matches
+const INITIAL_RATING: f64 = 1500.0;
+/// How much a single match can change a rating
+const RATING_FACTOR: f64 = 32.0;
+/// Iterations of PBKDF2 for new password hashes
+const PBKDF2_ROUNDS: u32 = 600_000;
+
+/// PBKDF2-HMAC-SHA256 hash of the password protecting a name, with a random salt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Password {
+    rounds: u32,
+    salt: [u8; 16],
+    hash: [u8; 32],
+}
+
+impl Password {
+    /// Hashes the password with a new random salt. Slow on purpose, so call it
+    /// off the async threads.
+    fn new(password: &str, rounds: u32) -> Password {
+        let salt = rand::random();
+        Password {
+            rounds,
+            salt,
+            hash: pbkdf2_sha256(password, &salt, rounds),
+        }
+    }
+
+    /// Slow on purpose, like [`Password::new`]
+    fn verify(&self, password: &str) -> bool {
+        self.hash == pbkdf2_sha256(password, &self.salt, self.rounds)
+    }
+
+    /// `-` for none, else `pbkdf2-sha256$<rounds>$<salt>$<hash>`, in hex
+    fn to_field(password: Option<Password>) -> String {
+        match password {
+            None => "-".to_string(),
+            Some(Password { rounds, salt, hash }) => {
+                format!("pbkdf2-sha256${rounds}${}${}", to_hex(&salt), to_hex(&hash))
+            }
+        }
+    }
+
+    fn from_field(field: &str) -> Option<Option<Password>> {
+        if field == "-" {
+            return Some(None);
+        }
+        match field.split('$').collect::<Vec<_>>()[..] {
+            ["pbkdf2-sha256", rounds, salt, hash] => Some(Some(Password {
+                rounds: rounds.parse().ok().filter(|&rounds| rounds > 0)?,
+                salt: from_hex(salt)?,
+                hash: from_hex(hash)?,
+            })),
+            _ => None,
+        }
+    }
+}
+
+fn pbkdf2_sha256(password: &str, salt: &[u8], rounds: u32) -> [u8; 32] {
+    let mut hash = [0; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, rounds, &mut hash);
+    hash
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 || !hex.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Player {
+    password: Option<Password>,
+    rating: f64,
+    wins: u16,
+    losses: u16,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Player {
+            password: None,
+            rating: INITIAL_RATING,
+            wins: 0,
+            losses: 0,
+        }
+    }
+}
+
+/// Probability of a player rated `rating` to beat one rated `opponent`
+fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// Known players with their passwords and Elo ratings, saved to a file after
+/// every change, if one is given
+#[derive(Debug)]
+pub struct Registry {
+    path: Option<path::PathBuf>,
+    players: HashMap<String, Player>,
+    connected: HashSet<String>,
+    /// Only these names may log in, if set
+    entrants: Option<HashSet<String>>,
+    /// Iterations of PBKDF2 for new password hashes
+    rounds: u32,
+    /// Number of the last [`Snapshot`] taken
+    version: u64,
+    /// Number of the last [`Snapshot`] written to the file
+    written: Arc<tokio::sync::Mutex<u64>>,
+}
+
+/// Registry shared by all connections of the server
+pub type SharedRegistry = Arc<Mutex<Registry>>;
+
+/// The content of the file at one point, taken under the lock of the registry
+/// and written outside of it
+#[derive(Debug)]
+#[must_use = "the file only changes once the snapshot is written"]
+pub struct Snapshot {
+    path: path::PathBuf,
+    version: u64,
+    content: String,
+    written: Arc<tokio::sync::Mutex<u64>>,
+}
+
+impl Snapshot {
+    /// Replaces the file at once, so a crash does not leave half of it. Skips
+    /// the write if a newer snapshot was already written.
+    pub async fn write(self) {
+        let mut written = self.written.lock().await;
+        if *written >= self.version {
+            return;
+        }
+        let tmp = self.path.with_extension("tmp");
+        let result = async {
+            fs::write(&tmp, self.content).await?;
+            fs::rename(tmp, &self.path).await
+        };
+        match result.await {
+            Ok(()) => *written = self.version,
+            Err(err) => tracing::warn!("{}", Error::Io(err)),
+        }
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry {
+            path: None,
+            players: HashMap::new(),
+            connected: HashSet::new(),
+            entrants: None,
+            rounds: PBKDF2_ROUNDS,
+            version: 0,
+            written: Arc::default(),
+        }
+    }
+}
+
+impl Registry {
+    /// Reads the players of the file. A missing file is an empty registry.
+    pub async fn load(path: Option<path::PathBuf>) -> Result<Registry, Error> {
+        let content = match &path {
+            Some(path) => match fs::read_to_string(path).await {
+                Ok(content) => content,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+                Err(err) => return Err(err.into()),
+            },
+            None => String::new(),
+        };
+
+        let players = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(i, line)| Registry::parse_line(line).ok_or(Error::InvalidEntry(i + 1)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Registry {
+            path,
+            players,
+            ..Registry::default()
+        })
+    }
+
+    /// name, rating, wins, losses and password hash, separated by tabs
+    fn parse_line(line: &str) -> Option<(String, Player)> {
+        let [name, rating, wins, losses, password] = line.split('\t').collect::<Vec<_>>()[..]
+        else {
+            return None;
+        };
+        if !protocol::is_valid_name(name) {
+            return None;
+        }
+
+        Some((
+            name.to_string(),
+            Player {
+                password: Password::from_field(password)?,
+                rating: rating.parse().ok()?,
+                wins: wins.parse().ok()?,
+                losses: losses.parse().ok()?,
+            },
+        ))
+    }
+
+    /// The content of the file after a change, `None` without a file
+    fn snapshot(&mut self) -> Option<Snapshot> {
+        let path = self.path.clone()?;
+
+        let mut content = String::new();
+        for (name, player) in &self.players {
+            content += &format!(
+                "{name}\t{}\t{}\t{}\t{}\n",
+                player.rating,
+                player.wins,
+                player.losses,
+                Password::to_field(player.password)
+            );
+        }
+
+        self.version += 1;
+        Some(Snapshot {
+            path,
+            version: self.version,
+            content,
+            written: self.written.clone(),
+        })
+    }
+
+    /// Turns away everyone not named, as for a tournament
+    pub fn restrict(&mut self, entrants: impl IntoIterator<Item = String>) {
+        self.entrants = Some(entrants.into_iter().collect());
+    }
+
+    /// Checks whether the name may log in right now, returning its password
+    /// and how new ones get hashed
+    fn check(&self, name: &str) -> Result<(Option<Password>, u32), server::Rejection> {
+        // the name of the computer player is reserved
+        if !protocol::is_valid_name(name) || name == crate::computer::NAME {
+            return Err(server::Rejection::InvalidName);
+        }
+        if self
+            .entrants
+            .as_ref()
+            .is_some_and(|entrants| !entrants.contains(name))
+        {
+            return Err(server::Rejection::NotEntered);
+        }
+        if self.connected.contains(name) {
+            return Err(server::Rejection::AlreadyConnected);
+        }
+        let password = self.players.get(name).and_then(|player| player.password);
+        Ok((password, self.rounds))
+    }
+
+    /// Checks the identity, and marks the name as connected until the
+    /// returned [`Login`] is dropped. The first password used with a name
+    /// claims it.
+    pub async fn login(
+        registry: &SharedRegistry,
+        identity: client::Identity,
+    ) -> Result<Login, server::Rejection> {
+        let (stored, rounds) = registry
+            .lock()
+            .expect("registry is never poisoned")
+            .check(&identity.name)?;
+
+        // hashing is slow on purpose, so it runs neither under the lock nor
+        // on the async threads
+        let password = identity.password;
+        let replacement = tokio::task::spawn_blocking(move || match (stored, password) {
+            (Some(stored), Some(password)) if stored.verify(&password) => Ok(None),
+            (Some(_), _) => Err(server::Rejection::WrongPassword),
+            (None, Some(password)) => Ok(Some(Password::new(&password, rounds))),
+            (None, None) => Ok(None),
+        })
+        .await
+        .expect("hashing does not panic")?;
+
+        let snapshot = {
+            let mut this = registry.lock().expect("registry is never poisoned");
+            // the name might have logged in or been claimed in the meantime
+            if this.check(&identity.name)?.0 != stored {
+                return Err(server::Rejection::WrongPassword);
+            }
+            this.connected.insert(identity.name.clone());
+            match replacement {
+                Some(password) => {
+                    this.players
+                        .entry(identity.name.clone())
+                        .or_default()
+                        .password = Some(password);
+                    this.snapshot()
+                }
+                None => None,
+            }
+        };
+        let login = Login {
+            registry: registry.clone(),
+            name: identity.name,
+        };
+        if let Some(snapshot) = snapshot {
+            snapshot.write().await;
+        }
+        Ok(login)
+    }
+
+    /// Logs the computer player in. Unlike clients, any number of computer
+    /// players can be connected at once.
+    pub fn login_computer(registry: &SharedRegistry) -> Login {
+        Login {
+            registry: registry.clone(),
+            name: crate::computer::NAME.to_string(),
+        }
+    }
+
+    /// Current rating of the player, the initial one for unknown players
+    pub fn rating(&self, name: &str) -> f64 {
+        self.players.get(name).map_or(INITIAL_RATING, |p| p.rating)
+    }
+
+    /// Updates the ratings after a finished match. The returned snapshot is
+    /// meant to be written once the lock is released.
+    pub fn record(&mut self, winner: &str, loser: &str) -> Option<Snapshot> {
+        let change =
+            RATING_FACTOR * (1.0 - expected_score(self.rating(winner), self.rating(loser)));
+
+        let winner = self.players.entry(winner.to_string()).or_default();
+        winner.rating += change;
+        winner.wins = winner.wins.saturating_add(1);
+        let loser = self.players.entry(loser.to_string()).or_default();
+        loser.rating -= change;
+        loser.losses = loser.losses.saturating_add(1);
+
+        self.snapshot()
+    }
+
+    /// The best rated players, who have played at least one match
+    pub fn leaderboard(&self) -> Vec<server::Standing> {
+        let mut players: Vec<_> = self
+            .players
+            .iter()
+            .filter(|(_, player)| player.wins > 0 || player.losses > 0)
+            .collect();
+        players.sort_by(|(a_name, a), (b_name, b)| {
+            b.rating.total_cmp(&a.rating).then(a_name.cmp(b_name))
+        });
+
+        players
+            .into_iter()
+            .take(server::LEADERBOARD_LEN)
+            .map(|(name, player)| server::Standing {
+                name: name.clone(),
+                rating: player.rating.round().clamp(0.0, u16::MAX as f64) as u16,
+                wins: player.wins,
+                losses: player.losses,
+            })
+            .collect()
+    }
+}
+
+/// A connected player, who gets disconnected from the registry when dropped
+#[derive(Debug)]
+pub struct Login {
+    registry: SharedRegistry,
+    name: String,
+}
+
+impl Login {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn registry(&self) -> &SharedRegistry {
+        &self.registry
+    }
+}
+
+impl Drop for Login {
+    fn drop(&mut self) {
+        self.registry
+            .lock()
+            .expect("registry is never poisoned")
+            .connected
+            .remove(&self.name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(name: &str, password: Option<&str>) -> client::Identity {
+        client::Identity {
+            name: name.to_string(),
+            password: password.map(String::from),
+        }
+    }
+
+    /// A registry hashing with a single round, to keep the tests fast
+    fn shared(path: Option<path::PathBuf>) -> SharedRegistry {
+        Arc::new(Mutex::new(Registry {
+            path,
+            rounds: 1,
+            ..Registry::default()
+        }))
+    }
+
+    #[test]
+    fn elo_ratings() {
+        let mut registry = Registry::default();
+        assert!(registry.record("ada", "bob").is_none());
+        // equal ratings expect a draw, so the winner gains half the factor
+        assert_eq!(registry.rating("ada"), INITIAL_RATING + RATING_FACTOR / 2.0);
+        assert_eq!(registry.rating("bob"), INITIAL_RATING - RATING_FACTOR / 2.0);
+
+        let before = (registry.rating("ada"), registry.rating("bob"));
+        let _ = registry.record("ada", "bob");
+        let favourite_gain = registry.rating("ada") - before.0;
+        assert_eq!(registry.rating("bob") - before.1, -favourite_gain);
+
+        let before = (registry.rating("ada"), registry.rating("bob"));
+        let _ = registry.record("bob", "ada");
+        let upset_gain = registry.rating("bob") - before.1;
+        assert_eq!(registry.rating("ada") - before.0, -upset_gain);
+        assert!(upset_gain > RATING_FACTOR / 2.0 && favourite_gain < RATING_FACTOR / 2.0);
+        assert!(upset_gain < RATING_FACTOR);
+
+        let standings = registry.leaderboard();
+        assert_eq!(standings[0].name, "ada");
+        assert_eq!((standings[0].wins, standings[0].losses), (2, 1));
+        assert_eq!((standings[1].wins, standings[1].losses), (1, 2));
+    }
+
+    #[tokio::test]
+    async fn passwords() {
+        let registry = shared(None);
+        drop(
+            Registry::login(&registry, identity("ada", None))
+                .await
+                .unwrap(),
+        );
+        // the first password claims the name
+        drop(
+            Registry::login(&registry, identity("ada", Some("sea")))
+                .await
+                .unwrap(),
+        );
+        drop(
+            Registry::login(&registry, identity("ada", Some("sea")))
+                .await
+                .unwrap(),
+        );
+        for password in [None, Some("ship")] {
+            assert_eq!(
+                Registry::login(&registry, identity("ada", password))
+                    .await
+                    .err(),
+                Some(server::Rejection::WrongPassword)
+            );
+        }
+        for name in ["not valid", crate::computer::NAME] {
+            assert_eq!(
+                Registry::login(&registry, identity(name, None)).await.err(),
+                Some(server::Rejection::InvalidName)
+            );
+        }
+
+        let password = registry.lock().unwrap().players["ada"].password.unwrap();
+        assert_eq!(password.rounds, 1);
+        assert!(password.verify("sea") && !password.verify("ship"));
+
+        // every hash gets its own salt
+        assert_ne!(Password::new("sea", 1), Password::new("sea", 1));
+    }
+
+    #[tokio::test]
+    async fn connected_until_dropped() {
+        let registry = shared(None);
+        let login = Registry::login(&registry, identity("ada", None))
+            .await
+            .unwrap();
+        assert_eq!(login.name(), "ada");
+        assert_eq!(
+            Registry::login(&registry, identity("ada", None))
+                .await
+                .err(),
+            Some(server::Rejection::AlreadyConnected)
+        );
+        drop(login);
+        assert!(Registry::login(&registry, identity("ada", None))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn saved_and_loaded() {
+        let path = std::env::temp_dir().join(format!("ziel-players-{}", std::process::id()));
+        let registry = shared(Some(path.clone()));
+        drop(
+            Registry::login(&registry, identity("ada", Some("sea")))
+                .await
+                .unwrap(),
+        );
+        let snapshot = registry.lock().unwrap().record("ada", "bob").unwrap();
+        // an older snapshot does not overwrite a newer one
+        let newer = registry.lock().unwrap().record("bob", "ada").unwrap();
+        newer.write().await;
+        snapshot.write().await;
+
+        let loaded = Registry::load(Some(path.clone())).await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().players, registry.lock().unwrap().players);
+
+        for line in [
+            "ada\t1500\t0\t0",
+            "not valid\t1500\t0\t0\t-",
+            "ada\tstrong\t0\t0\t-",
+            "ada\t1500\t0\t0\tpbkdf2-sha256$0$00$00",
+            "ada\t1500\t0\t0\tabc",
+        ] {
+            assert_eq!(Registry::parse_line(line), None, "{line}");
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::VecDeque;
+
+use tokio::{
+    sync::{mpsc, watch},
+    task, time,
+};
+
+use protocol::{client, session::Incoming};
+
+use crate::game::Player;
+
+/// Number of chat messages a client may send within [`CHAT_RATE_WINDOW`]
+const CHAT_RATE_LIMIT: usize = 5;
+const CHAT_RATE_WINDOW: time::Duration = time::Duration::from_secs(10);
+
+/// Handles the messages both clients send on their own accord, for the whole
+/// series. Chat messages get relayed to the opponent right away, surrenders
+/// get passed on to the running game. A player who is not a client, like the
+/// computer, sends nothing and gets no chat.
+pub struct Relay {
+    surrenders: mpsc::UnboundedReceiver<Player>,
+    stop: watch::Sender<()>,
+    /// Returns the inboxes of the clients still connected
+    task: Option<task::JoinHandle<[Option<crate::stream::Inbox>; 2]>>,
+}
+
+impl Relay {
+    /// Relays for the players who are clients, indexed by [`Player::index`]
+    pub fn spawn(
+        clients: [Option<(crate::Stream, crate::stream::Inbox)>; 2],
+        registry: &crate::players::SharedRegistry,
+    ) -> Relay {
+        let (surrender_sender, surrenders) = mpsc::unbounded_channel();
+        let (stop, stopped) = watch::channel(());
+        let streams = clients
+            .each_ref()
+            .map(|client| client.as_ref().map(|(stream, _)| stream.clone()));
+        let [client1, client2] = clients;
+        let task = tokio::spawn({
+            let registry = registry.clone();
+            async move {
+                let [stream1, stream2] = streams;
+                let (inbox1, inbox2) = tokio::join!(
+                    relay(
+                        Player::One,
+                        client1,
+                        stream2,
+                        &registry,
+                        surrender_sender.clone(),
+                        stopped.clone(),
+                    ),
+                    relay(
+                        Player::Two,
+                        client2,
+                        stream1,
+                        &registry,
+                        surrender_sender,
+                        stopped
+                    ),
+                );
+                [inbox1, inbox2]
+            }
+        });
+        Relay {
+            surrenders,
+            stop,
+            task: Some(task),
+        }
+    }
+
+    /// Stops relaying and hands back the inboxes of the clients, which are
+    /// `None` for disconnected clients
+    pub async fn finish(mut self) -> [Option<crate::stream::Inbox>; 2] {
+        let _ = self.stop.send(());
+        self.task
+            .take()
+            .expect("task is only taken here")
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Discards surrenders left over from the previous match
+    pub fn clear_surrenders(&mut self) {
+        while self.surrenders.try_recv().is_ok() {}
+    }
+
+    /// Waits for a player to surrender. `None` once both clients disconnected.
+    pub async fn surrender(&mut self) -> Option<Player> {
+        self.surrenders.recv().await
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+/// Remembers when the recent chat messages of a client were received
+#[derive(Default)]
+struct ChatLimit {
+    times: VecDeque<time::Instant>,
+}
+
+impl ChatLimit {
+    /// Prepares the chat message for relaying, unless the client exceeds the rate limit
+    fn receive(&mut self, text: String) -> Option<String> {
+        let now = time::Instant::now();
+        while let Some(&sent) = self.times.front() {
+            if now.duration_since(sent) < CHAT_RATE_WINDOW {
+                break;
+            }
+            self.times.pop_front();
+        }
+
+        if self.times.len() >= CHAT_RATE_LIMIT {
+            tracing::warn!("chat rate limit exceeded, message dropped");
+            return None;
+        }
+        self.times.push_back(now);
+        // control characters could mess with the terminal of the opponent
+        Some(text.chars().filter(|c| !c.is_control()).collect())
+    }
+}
+
+/// Relays the messages of `client`, until stopped. `None` if the client
+/// disconnected, or the player is no client at all.
+async fn relay(
+    player: Player,
+    client: Option<(crate::Stream, crate::stream::Inbox)>,
+    opponent: Option<crate::Stream>,
+    registry: &crate::players::SharedRegistry,
+    surrenders: mpsc::UnboundedSender<Player>,
+    mut stopped: watch::Receiver<()>,
+) -> Option<crate::stream::Inbox> {
+    let (stream, mut inbox) = client?;
+    let mut chat_limit = ChatLimit::default();
+    loop {
+        let incoming = tokio::select! {
+            _ = stopped.changed() => return Some(inbox),
+            incoming = inbox.recv() => incoming,
+        };
+        match incoming {
+            Ok(Incoming::Message(client::Message::Surrender)) => {
+                let _ = surrenders.send(player);
+            }
+            Ok(Incoming::Message(client::Message::Chat(text))) => {
+                let Some(text) = chat_limit.receive(text) else {
+                    continue;
+                };
+                // a disconnected opponent ends the match on its own
+                if let Some(Err(err)) = opponent.as_ref().map(|opponent| opponent.inform_chat(text))
+                {
+                    tracing::warn!("relay :: {player:?} :: {err}");
+                }
+            }
+            Ok(Incoming::Request(id, client::Message::RequestLeaderboard)) => {
+                let standings = registry
+                    .lock()
+                    .expect("registry is never poisoned")
+                    .leaderboard();
+                if let Err(err) = stream.respond_leaderboard(id, standings) {
+                    tracing::warn!("relay :: {player:?} :: {err}");
+                    return None;
+                }
+            }
+            Ok(Incoming::Message(res) | Incoming::Request(_, res)) => {
+                let err = crate::stream::Error::ClientMessageUnexpected(res);
+                tracing::warn!("relay :: {player:?} :: {err}");
+            }
+            Err(protocol::Error::Protocol(err)) => {
+                tracing::warn!("relay :: {player:?} :: invalid message :: {err}");
+            }
+            Err(err) => {
+                tracing::info!("relay :: {player:?} :: connection closed :: {err}");
+                return None;
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::path;
+
+use crate::game::{Outcome, Player};
+
+/// A readable record of every shot of a finished match
+fn to_text(players: [&str; 2], outcome: &Outcome) -> String {
+    let name = |player: Player| players[player.index()];
+    let mut text = format!("{} vs {}\n\n", players[0], players[1]);
+
+    for (player, ships) in [Player::One, Player::Two].into_iter().zip(outcome.ships) {
+        text += &format!("{} ships:", name(player));
+        for ship in ships.into_ship_array() {
+            text += &format!(" {ship}");
+        }
+        text += "\n";
+    }
+    text += "\n";
+
+    for (turn, shot) in outcome.moves.iter().enumerate() {
+        let result = match shot.attack_info {
+            logic::board::AttackInfo::Miss => "miss",
+            logic::board::AttackInfo::Hit(None) => "hit",
+            logic::board::AttackInfo::Hit(Some(_)) => "hit, sunk",
+        };
+        text += &format!(
+            "{:>3} {:<16} {:>3} {result}\n",
+            turn + 1,
+            name(shot.player),
+            shot.target
+        );
+    }
+
+    text += &format!("\n{} won", name(outcome.winner));
+    if outcome.surrendered {
+        text += &format!(", {} surrendered", name(outcome.winner.other()));
+    }
+    text += "\n";
+    text
+}
+
+/// Writes the replay of the match to `path`, replacing an older file
+pub async fn save(
+    path: &path::Path,
+    players: [&str; 2],
+    outcome: &Outcome,
+) -> Result<(), std::io::Error> {
+    tokio::fs::write(path, to_text(players, outcome)).await
+}
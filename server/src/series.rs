@@ -0,0 +1,232 @@
+use std::time;
+
+use crate::{
+    computer::Computer,
+    game::{Game, Outcome, Player},
+    history,
+    player::Player as _,
+    relay::Relay,
+};
+
+/// A series of matches between two players, played over the same connections.
+///
+/// After every match the score is sent to both players. As long as the series
+/// is not decided, both players get asked for a rematch, and only if both
+/// accept, the next match begins with a new ship placement.
+///
+/// The first player is always a client, the second one a client or the
+/// [`Computer`].
+pub struct Series<P = crate::Stream> {
+    player1: crate::Stream,
+    player2: P,
+    relay: Relay,
+    players: [crate::players::Login; 2],
+
+    best_of: Option<u8>,
+    swap_first: bool,
+    history: Option<std::path::PathBuf>,
+    score: [u8; 2],
+}
+
+impl Series {
+    pub fn new(
+        (stream1, inbox1, login1): crate::stream::Connection,
+        (stream2, inbox2, login2): crate::stream::Connection,
+        config: &crate::Config,
+    ) -> Series {
+        let relay = Relay::spawn(
+            [
+                Some((stream1.clone(), inbox1)),
+                Some((stream2.clone(), inbox2)),
+            ],
+            login1.registry(),
+        );
+        Series::start(stream1, stream2, relay, [login1, login2], config)
+    }
+
+    /// Ends the series without terminating the connections. Disconnected
+    /// clients are `None`.
+    pub async fn finish(self) -> [Option<crate::stream::Connection>; 2] {
+        let [inbox1, inbox2] = self.relay.finish().await;
+        let [login1, login2] = self.players;
+        [
+            inbox1.map(|inbox| (self.player1, inbox, login1)),
+            inbox2.map(|inbox| (self.player2, inbox, login2)),
+        ]
+    }
+}
+
+impl Series<Computer> {
+    /// A series of the client against the computer
+    pub fn against_computer(
+        (stream, inbox, login): crate::stream::Connection,
+        computer: Computer,
+        config: &crate::Config,
+    ) -> Series<Computer> {
+        let relay = Relay::spawn([Some((stream.clone(), inbox)), None], login.registry());
+        let computer_login = crate::players::Registry::login_computer(login.registry());
+        Series::start(stream, computer, relay, [login, computer_login], config)
+    }
+}
+
+impl<P: crate::player::Player> Series<P> {
+    fn start(
+        player1: crate::Stream,
+        player2: P,
+        relay: Relay,
+        players: [crate::players::Login; 2],
+        config: &crate::Config,
+    ) -> Series<P> {
+        Series {
+            player1,
+            player2,
+            relay,
+            players,
+            best_of: config.best_of,
+            swap_first: config.swap_first,
+            history: config.history.clone(),
+            score: [0, 0],
+        }
+    }
+
+    /// `true` if one player has won the majority of the `best_of` matches
+    fn is_decided(&self) -> bool {
+        match self.best_of {
+            Some(best_of) => self.score.iter().any(|&wins| wins > best_of / 2),
+            None => false,
+        }
+    }
+
+    fn score_for(&self, player: Player) -> protocol::server::Score {
+        let (client, opponent) = match player {
+            Player::One => (self.score[0], self.score[1]),
+            Player::Two => (self.score[1], self.score[0]),
+        };
+        protocol::server::Score {
+            client,
+            opponent,
+            best_of: self.best_of,
+        }
+    }
+
+    async fn play_match(&mut self, first: Player) -> Result<Outcome, crate::stream::Error> {
+        let started = (time::SystemTime::now(), time::Instant::now());
+        self.relay.clear_surrenders();
+        let game = Game::new(&self.player1, &self.player2, first).await?;
+        tracing::info!("board initialization successful");
+
+        let outcome = game.play(&mut self.relay).await?;
+        let wins = &mut self.score[outcome.winner.index()];
+        *wins = wins.saturating_add(1);
+
+        let (winner, loser) = (
+            &self.players[outcome.winner.index()],
+            &self.players[outcome.winner.other().index()],
+        );
+        let snapshot = winner
+            .registry()
+            .lock()
+            .expect("registry is never poisoned")
+            .record(winner.name(), loser.name());
+        if let Some(snapshot) = snapshot {
+            snapshot.write().await;
+        }
+
+        if let Some(path) = &self.history {
+            let record = history::MatchRecord {
+                players: self
+                    .players
+                    .each_ref()
+                    .map(|login| login.name().to_string()),
+                started: started
+                    .0
+                    .duration_since(time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                duration: started.1.elapsed(),
+                best_of: self.best_of,
+                first: first.index(),
+                shots: outcome.shots,
+                hits: outcome.hits,
+                winner: outcome.winner.index(),
+                end: if outcome.surrendered {
+                    history::End::Surrender
+                } else {
+                    history::End::Sunk
+                },
+            };
+            // losing the record is no reason to end the series
+            if let Err(err) = history::append(path, &record).await {
+                tracing::warn!("{err}");
+            }
+        }
+
+        let (score1, score2) = (self.score_for(Player::One), self.score_for(Player::Two));
+        let (success1, success2) = tokio::join!(
+            self.player1.request_inform_score(score1),
+            self.player2.request_inform_score(score2),
+        );
+        success1?;
+        success2?;
+
+        Ok(outcome)
+    }
+
+    /// Plays matches until the series is decided or a player declines the rematch.
+    /// The connections get terminated afterwards.
+    pub async fn play(mut self) -> Result<(), crate::stream::Error> {
+        let mut first = Player::One;
+        loop {
+            let winner = self.play_match(first).await?.winner;
+            tracing::info!(
+                "match finished :: winner {winner:?} :: score {}-{}",
+                self.score[0],
+                self.score[1]
+            );
+
+            if self.is_decided() {
+                tracing::info!("series decided");
+                break;
+            }
+
+            let (accept1, accept2) = tokio::join!(
+                self.player1.request_rematch(),
+                self.player2.request_rematch(),
+            );
+            if !(accept1? && accept2?) {
+                tracing::info!("rematch declined");
+                break;
+            }
+
+            if self.swap_first {
+                first = first.other();
+            }
+        }
+
+        let (success1, success2) = tokio::join!(
+            self.player1.request_terminate(),
+            self.player2.request_terminate(),
+        );
+        success1?;
+        success2?;
+
+        Ok(())
+    }
+
+    /// Plays matches until the series is decided, without asking for
+    /// rematches. A single match decides a series without `best_of`. The
+    /// connections stay open, see [`Series::finish`].
+    pub async fn play_decided(&mut self) -> Result<Vec<Outcome>, crate::stream::Error> {
+        let mut first = Player::One;
+        let mut outcomes = vec![];
+        loop {
+            outcomes.push(self.play_match(first).await?);
+            if self.is_decided() || self.best_of.is_none() {
+                break Ok(outcomes);
+            }
+            if self.swap_first {
+                first = first.other();
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
-use tokio::{io, net};
+use tokio::io;
 
-use protocol::{client, server};
+use protocol::{client, server, session::Incoming};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -14,6 +14,27 @@ pub enum Error {
     ClientResponseUnexpected(server::Message, client::Message),
     #[error("server :: client response :: logic :: {0}")]
     ClientResponseTargetAlreadyHit(#[from] logic::board::AlreadyHitError),
+    #[error("{0}")]
+    Players(#[from] crate::players::Error),
+    #[error("server :: handshake rejected :: {0}")]
+    HandshakeRejected(server::Rejection),
+    #[error("server :: client message :: unexpected message :: {0:?}")]
+    ClientMessageUnexpected(client::Message),
+    #[error("server :: computer :: unknown strategy :: {0}")]
+    UnknownStrategy(String),
+    #[error("server :: game :: {0}")]
+    Game(logic::game::CommandError),
+}
+
+impl From<logic::game::CommandError> for Error {
+    fn from(err: logic::game::CommandError) -> Error {
+        match err {
+            logic::game::CommandError::AlreadyHit(err) => {
+                Error::ClientResponseTargetAlreadyHit(err)
+            }
+            err => Error::Game(err),
+        }
+    }
 }
 
 pub enum Response {
@@ -21,31 +42,65 @@ pub enum Response {
 
     ReturnShips(logic::Ships),
     ReturnTarget(logic::Position),
+    ReturnRematch(bool),
 }
 
+/// Messages the client sends on its own accord
+pub type Inbox = protocol::session::Receiver<client::Message>;
+
+/// A logged in client, as returned by [`Stream::handshake`]
+pub type Connection = (Stream, Inbox, crate::players::Login);
+
+/// Sends requests to a client. All clones share the same connection.
+#[derive(Clone)]
 pub struct Stream {
-    stream: net::TcpStream,
+    session: protocol::session::Sender<server::Message, client::Message>,
 }
 
 impl Stream {
-    pub async fn handshake(mut stream: net::TcpStream) -> Result<Stream, Error> {
-        match protocol::read(&mut stream).await? {
-            client::Message::HandShake => {
-                protocol::write(&mut stream, server::Message::Handshake).await?
+    /// Logs the client in with the identity it sends. A rejected client gets
+    /// told why, before the connection is closed.
+    pub async fn handshake(
+        stream: impl io::AsyncRead + io::AsyncWrite + Send + 'static,
+        heartbeat: protocol::session::Heartbeat,
+        registry: &crate::players::SharedRegistry,
+    ) -> Result<Connection, Error> {
+        let (Stream { session }, mut inbox) = Stream::start(stream, heartbeat);
+        let login = match inbox.recv().await? {
+            Incoming::Request(id, client::Message::HandShake(identity)) => {
+                match crate::players::Registry::login(registry, identity).await {
+                    Ok(login) => {
+                        session.respond(id, server::Message::Handshake)?;
+                        login
+                    }
+                    Err(rejection) => {
+                        session.respond(id, server::Message::RejectHandshake(rejection))?;
+                        session.close().await?;
+                        return Err(Error::HandshakeRejected(rejection));
+                    }
+                }
             }
-            res => {
+            Incoming::Request(_, res) | Incoming::Message(res) => {
                 return Err(Error::ClientResponseUnexpected(
                     server::Message::Handshake,
                     res,
                 ))
             }
-        }
-        Ok(Stream { stream })
+        };
+        Ok((Stream { session }, inbox, login))
     }
 
-    pub async fn request(&mut self, req: protocol::server::Message) -> Result<Response, Error> {
-        protocol::write(&mut self.stream, req.clone()).await?;
-        let res = protocol::read(&mut self.stream).await?;
+    /// Starts the session, without logging the client in
+    pub fn start(
+        stream: impl io::AsyncRead + io::AsyncWrite + Send + 'static,
+        heartbeat: protocol::session::Heartbeat,
+    ) -> (Stream, Inbox) {
+        let (session, inbox) = protocol::session::start(stream, heartbeat);
+        (Stream { session }, inbox)
+    }
+
+    pub async fn request(&self, req: protocol::server::Message) -> Result<Response, Error> {
+        let res = self.session.request(req.clone()).await?;
 
         match (req, res) {
             (
@@ -59,7 +114,8 @@ impl Stream {
                 | server::Message::InformShipSunkenClient(..)
                 | server::Message::InformShipSunkenOpponent(..)
                 | server::Message::InformLoss
-                | server::Message::InformVictory,
+                | server::Message::InformVictory
+                | server::Message::InformScore(..),
                 client::Message::Acknowledge,
             ) => Ok(Response::Successful),
             (server::Message::RequestShips, client::Message::ReturnShips(ships)) => {
@@ -68,47 +124,100 @@ impl Stream {
             (server::Message::RequestTarget, client::Message::ReturnTarget(target)) => {
                 Ok(Response::ReturnTarget(target))
             }
+            (server::Message::RequestRematch, client::Message::ReturnRematch(accept)) => {
+                Ok(Response::ReturnRematch(accept))
+            }
             (req, res) => Err(Error::ClientResponseUnexpected(req, res)),
         }
     }
 
-    pub async fn request_board(&mut self) -> Result<logic::Ships, Error> {
+    /// Closes the connection, after everything sent so far is written
+    pub async fn close(&self) -> Result<(), Error> {
+        Ok(self.session.close().await?)
+    }
+
+    /// Answers a leaderboard request of the client
+    pub fn respond_leaderboard(
+        &self,
+        id: protocol::session::RequestId,
+        standings: Vec<server::Standing>,
+    ) -> Result<(), Error> {
+        Ok(self
+            .session
+            .respond(id, server::Message::ReturnLeaderboard(standings))?)
+    }
+
+    /// Tells the waiting client its place in the matchmaking queue, no
+    /// response is expected
+    pub fn inform_queue(&self, status: server::QueueStatus) -> Result<(), Error> {
+        Ok(self.session.send(server::Message::InformQueue(status))?)
+    }
+
+    /// Sends a chat message of the opponent, no response is expected
+    pub fn inform_chat(&self, text: String) -> Result<(), Error> {
+        Ok(self.session.send(server::Message::InformChat(text))?)
+    }
+}
+
+impl crate::player::Player for Stream {
+    async fn request_board(&self) -> Result<logic::Ships, Error> {
         match self.request(server::Message::RequestShips).await? {
             Response::ReturnShips(ships) => Ok(ships),
             _ => unreachable!("request match statement fallible"),
         }
     }
 
-    pub async fn request_target(&mut self) -> Result<logic::Position, Error> {
+    async fn request_target(&self) -> Result<logic::Position, Error> {
         match self.request(server::Message::RequestTarget).await? {
             Response::ReturnTarget(target) => Ok(target),
             _ => unreachable!("request match statement fallible"),
         }
     }
 
-    pub async fn request_inform_target_selection(&mut self) -> Result<(), Error> {
+    async fn request_inform_target_selection(&self) -> Result<(), Error> {
         match self.request(server::Message::InformTargetSelection).await? {
             Response::Successful => Ok(()),
             _ => unreachable!("request match statement fallible"),
         }
     }
 
-    pub async fn request_inform_victory(&mut self) -> Result<(), Error> {
+    async fn request_inform_victory(&self) -> Result<(), Error> {
         match self.request(server::Message::InformVictory).await? {
             Response::Successful => Ok(()),
             _ => unreachable!("request match statement fallible"),
         }
     }
 
-    pub async fn request_inform_loss(&mut self) -> Result<(), Error> {
+    async fn request_inform_loss(&self) -> Result<(), Error> {
         match self.request(server::Message::InformLoss).await? {
             Response::Successful => Ok(()),
             _ => unreachable!("request match statement fallible"),
         }
     }
 
-    pub async fn request_inform_attack_info_client(
-        &mut self,
+    async fn request_inform_score(&self, score: server::Score) -> Result<(), Error> {
+        match self.request(server::Message::InformScore(score)).await? {
+            Response::Successful => Ok(()),
+            _ => unreachable!("request match statement fallible"),
+        }
+    }
+
+    async fn request_rematch(&self) -> Result<bool, Error> {
+        match self.request(server::Message::RequestRematch).await? {
+            Response::ReturnRematch(accept) => Ok(accept),
+            _ => unreachable!("request match statement fallible"),
+        }
+    }
+
+    async fn request_terminate(&self) -> Result<(), Error> {
+        match self.request(server::Message::TerminateConnection).await? {
+            Response::Successful => Ok(()),
+            _ => unreachable!("request match statement fallible"),
+        }
+    }
+
+    async fn request_inform_attack_info_client(
+        &self,
         attack_info: logic::board::AttackInfo,
         pos: logic::Position,
     ) -> Result<(), Error> {
@@ -150,8 +259,8 @@ impl Stream {
         }
     }
 
-    pub async fn request_inform_attack_info_opponent(
-        &mut self,
+    async fn request_inform_attack_info_opponent(
+        &self,
         attack_info: logic::board::AttackInfo,
         pos: logic::Position,
     ) -> Result<(), Error> {
@@ -0,0 +1,652 @@
+use std::{
+    path,
+    sync::{Arc, Mutex},
+};
+
+use tokio::{net, task, time};
+
+use crate::{
+    game::{Outcome, Player},
+    idle::Idle,
+    player::Player as _,
+    players::SharedRegistry,
+    stream::Connection,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("server :: tournament :: io :: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Players(#[from] crate::players::Error),
+    #[error("server :: tournament :: at least two players are needed")]
+    TooFewEntrants,
+    #[error("server :: tournament :: invalid name :: {0}")]
+    InvalidName(String),
+    #[error("server :: tournament :: entered twice :: {0}")]
+    DuplicateEntrant(String),
+    #[error("server :: tournament :: {0} swiss rounds :: at least 1 and at most {1}")]
+    InvalidRounds(usize, usize),
+}
+
+/// How the pairings of the rounds are decided
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Every player plays every other player once
+    RoundRobin,
+    /// Players with equal wins play each other, without repeating pairings
+    /// unless every way to pair the round repeats one. An odd player out gets
+    /// a bye, which counts as a win.
+    Swiss { rounds: usize },
+}
+
+impl Format {
+    /// Swiss rounds needed to leave a single player without a loss
+    pub fn swiss_rounds(entrants: usize) -> usize {
+        entrants.next_power_of_two().trailing_zeros() as usize
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Names of the players. The tournament begins once all of them connected,
+    /// nobody else is let in.
+    pub entrants: Vec<String>,
+    /// Begins the tournament after this long with the players connected by
+    /// then, if there are at least two. The missing ones are withdrawn.
+    pub start_after: Option<time::Duration>,
+    pub format: Format,
+    /// Directory a replay of every match gets written to
+    pub replays: Option<path::PathBuf>,
+    /// Settings for the series of every pairing. Without `best_of`, a single
+    /// match decides a pairing.
+    pub series: crate::Config,
+}
+
+/// Result of a player at the end of the tournament
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Standing {
+    pub name: String,
+    /// Pairings won, including byes and opponents who did not show up
+    pub wins: u32,
+    pub losses: u32,
+    pub byes: u32,
+    /// Matches won and lost over all pairings
+    pub match_wins: u32,
+    pub match_losses: u32,
+    /// Sum of the wins of all opponents, breaks ties
+    pub buchholz: u32,
+    /// Disconnected before the tournament was over
+    pub withdrawn: bool,
+}
+
+struct Entrant {
+    standing: Standing,
+    /// Indices of all players paired against so far
+    opponents: Vec<usize>,
+    /// `None` before the player connected, while playing and once withdrawn
+    idle: Option<Idle>,
+}
+
+struct Tournament {
+    entrants: Vec<Entrant>,
+    registry: SharedRegistry,
+    config: Config,
+}
+
+/// Hosts the tournament at `addr` and returns the final standings, the best
+/// player first.
+pub async fn run(addr: std::net::SocketAddr, config: Config) -> Result<Vec<Standing>, Error> {
+    for (i, name) in config.entrants.iter().enumerate() {
+        if !protocol::is_valid_name(name) {
+            return Err(Error::InvalidName(name.clone()));
+        }
+        if config.entrants[..i].contains(name) {
+            return Err(Error::DuplicateEntrant(name.clone()));
+        }
+    }
+    if config.entrants.len() < 2 {
+        return Err(Error::TooFewEntrants);
+    }
+    if let Format::Swiss { rounds } = config.format {
+        let most = config.entrants.len() - 1;
+        if !(1..=most).contains(&rounds) {
+            return Err(Error::InvalidRounds(rounds, most));
+        }
+    }
+    if let Some(replays) = &config.replays {
+        tokio::fs::create_dir_all(replays).await?;
+    }
+
+    let mut registry = crate::players::Registry::load(config.series.players.clone()).await?;
+    registry.restrict(config.entrants.iter().cloned());
+    let mut tournament = Tournament {
+        entrants: config
+            .entrants
+            .iter()
+            .map(|name| Entrant {
+                standing: Standing {
+                    name: name.clone(),
+                    ..Default::default()
+                },
+                opponents: vec![],
+                idle: None,
+            })
+            .collect(),
+        registry: Arc::new(Mutex::new(registry)),
+        config,
+    };
+
+    let listener = net::TcpListener::bind(addr).await?;
+    tracing::info!(
+        "TOURNAMENT waiting for {} players at [{addr}]",
+        tournament.entrants.len()
+    );
+    tournament.gather(&listener).await?;
+
+    let schedule = match tournament.config.format {
+        Format::RoundRobin => round_robin(tournament.entrants.len()),
+        Format::Swiss { rounds } => vec![vec![]; rounds],
+    };
+    for (round, pairings) in schedule.into_iter().enumerate() {
+        let pairings = match tournament.config.format {
+            Format::RoundRobin => pairings,
+            Format::Swiss { .. } => tournament.swiss_pairings(),
+        };
+        tracing::info!("TOURNAMENT round {}", round + 1);
+        tournament.play_round(round + 1, pairings).await;
+    }
+
+    tournament.terminate().await;
+    Ok(tournament.standings())
+}
+
+/// Pairings of every round, so every player meets every other player once.
+/// The first player stays in place, while the others rotate around them.
+fn round_robin(players: usize) -> Vec<Vec<(usize, Option<usize>)>> {
+    let mut circle: Vec<Option<usize>> = (0..players).map(Some).collect();
+    if players % 2 == 1 {
+        circle.push(None);
+    }
+    let len = circle.len();
+
+    (1..len)
+        .map(|_| {
+            let round = (0..len / 2)
+                .filter_map(|i| match (circle[i], circle[len - 1 - i]) {
+                    (Some(a), Some(b)) => Some((a, Some(b))),
+                    // everyone sits out once, so nobody gains from it
+                    _ => None,
+                })
+                .collect();
+            circle[1..].rotate_right(1);
+            round
+        })
+        .collect()
+}
+
+/// Pairs the ranked players from the top, each with the best ranked opponent
+/// they have not `met`, backtracking where that leaves the rest unpairable.
+/// `None` if every way to pair them repeats a pairing.
+fn pair_unmet(
+    ranking: &[usize],
+    met: &impl Fn(usize, usize) -> bool,
+) -> Option<Vec<(usize, Option<usize>)>> {
+    let Some((&player, rest)) = ranking.split_first() else {
+        return Some(vec![]);
+    };
+    for (i, &opponent) in rest.iter().enumerate() {
+        if met(player, opponent) {
+            continue;
+        }
+        let mut others = rest.to_vec();
+        others.remove(i);
+        if let Some(mut pairings) = pair_unmet(&others, met) {
+            pairings.insert(0, (player, Some(opponent)));
+            return Some(pairings);
+        }
+    }
+    None
+}
+
+async fn play_pairing(
+    [player1, player2]: [Connection; 2],
+    config: crate::Config,
+) -> (
+    Result<Vec<Outcome>, crate::stream::Error>,
+    [Option<Connection>; 2],
+) {
+    let mut series = crate::series::Series::new(player1, player2, &config);
+    let result = series.play_decided().await;
+    (result, series.finish().await)
+}
+
+impl Tournament {
+    /// Lets the entrants log in, until all of them are connected or the
+    /// start is due
+    async fn gather(&mut self, listener: &net::TcpListener) -> Result<(), Error> {
+        let mut handshakes = task::JoinSet::new();
+        let mut interval = time::interval(time::Duration::from_secs(1));
+        let start = self
+            .config
+            .start_after
+            .map(|after| time::Instant::now() + after);
+        loop {
+            let connected = self
+                .entrants
+                .iter()
+                .filter(|entrant| entrant.idle.is_some())
+                .count();
+            if connected == self.entrants.len() {
+                break;
+            }
+            if connected >= 2 && start.is_some_and(|start| time::Instant::now() >= start) {
+                for entrant in &mut self.entrants {
+                    if entrant.idle.is_none() {
+                        tracing::info!("TOURNAMENT {} did not show up", entrant.standing.name);
+                        entrant.standing.withdrawn = true;
+                    }
+                }
+                break;
+            }
+
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, addr) = accepted?;
+                    tracing::info!("ACCEPTED [{addr}]");
+                    let registry = self.registry.clone();
+                    let heartbeat = self.config.series.heartbeat;
+                    handshakes.spawn(async move {
+                        (addr, crate::Stream::handshake(stream, heartbeat, &registry).await)
+                    });
+                }
+                Some(Ok((addr, handshake))) = handshakes.join_next() => match handshake {
+                    Ok(connection) => {
+                        let entrant = self
+                            .entrants
+                            .iter_mut()
+                            .find(|entrant| entrant.standing.name == connection.2.name())
+                            .expect("only entrants can log in");
+                        tracing::info!("TOURNAMENT {} entered", entrant.standing.name);
+                        entrant.idle = Some(Idle::new(connection, &self.registry));
+                    }
+                    Err(err) => tracing::warn!("[{addr}] {err}"),
+                },
+                _ = interval.tick() => {}
+            }
+
+            // frees the name, so the player can connect again
+            for entrant in &mut self.entrants {
+                if entrant
+                    .idle
+                    .as_ref()
+                    .is_some_and(|idle| !idle.is_connected())
+                {
+                    tracing::info!("TOURNAMENT {} left", entrant.standing.name);
+                    entrant.idle = None;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn buchholz(&self, player: usize) -> u32 {
+        self.entrants[player]
+            .opponents
+            .iter()
+            .map(|&opponent| self.entrants[opponent].standing.wins)
+            .sum()
+    }
+
+    /// Indices of all players, the best first
+    fn ranking(&self) -> Vec<usize> {
+        let mut ranking: Vec<usize> = (0..self.entrants.len()).collect();
+        ranking.sort_by_cached_key(|&i| {
+            let standing = &self.entrants[i].standing;
+            (
+                std::cmp::Reverse(standing.wins),
+                std::cmp::Reverse(self.buchholz(i)),
+                std::cmp::Reverse(standing.match_wins as i64 - standing.match_losses as i64),
+                standing.name.clone(),
+            )
+        });
+        ranking
+    }
+
+    /// Pairs players of the same rank, who have not met yet if possible
+    fn swiss_pairings(&self) -> Vec<(usize, Option<usize>)> {
+        let mut ranking: Vec<usize> = self
+            .ranking()
+            .into_iter()
+            .filter(|&i| !self.entrants[i].standing.withdrawn)
+            .collect();
+
+        let mut pairings = vec![];
+        if ranking.len() % 2 == 1 {
+            // the lowest ranked player without a bye sits out
+            let bye = ranking
+                .iter()
+                .rposition(|&i| self.entrants[i].standing.byes == 0)
+                .unwrap_or(ranking.len() - 1);
+            pairings.push((ranking.remove(bye), None));
+        }
+        let met =
+            |player: usize, opponent: usize| self.entrants[player].opponents.contains(&opponent);
+        match pair_unmet(&ranking, &met) {
+            Some(unmet) => pairings.extend(unmet),
+            None => pairings.extend(ranking.chunks(2).map(|pair| (pair[0], Some(pair[1])))),
+        }
+        pairings
+    }
+
+    /// Records a pairing that could not be played out. A player who is still
+    /// connected wins against a disconnected one.
+    fn forfeit(&mut self, [player1, player2]: [usize; 2], connected: [bool; 2]) {
+        match connected {
+            [true, false] => self.record(player1, player2),
+            [false, true] => self.record(player2, player1),
+            // nobody to blame, or nobody left to win
+            _ => {}
+        }
+    }
+
+    fn record(&mut self, winner: usize, loser: usize) {
+        self.entrants[winner].standing.wins += 1;
+        self.entrants[winner].opponents.push(loser);
+        self.entrants[loser].standing.losses += 1;
+        self.entrants[loser].opponents.push(winner);
+    }
+
+    async fn play_round(&mut self, round: usize, pairings: Vec<(usize, Option<usize>)>) {
+        let mut pairings_played = task::JoinSet::new();
+        for (player1, player2) in pairings {
+            let Some(player2) = player2 else {
+                tracing::info!("TOURNAMENT {} has a bye", self.name(player1));
+                self.entrants[player1].standing.wins += 1;
+                self.entrants[player1].standing.byes += 1;
+                continue;
+            };
+
+            let (connection1, connection2) = (
+                self.take_connection(player1).await,
+                self.take_connection(player2).await,
+            );
+            match (connection1, connection2) {
+                (Some(connection1), Some(connection2)) => {
+                    tracing::info!("MATCH {} vs {}", self.name(player1), self.name(player2));
+                    let config = self.config.series.clone();
+                    pairings_played.spawn(async move {
+                        let (result, connections) =
+                            play_pairing([connection1, connection2], config).await;
+                        ([player1, player2], result, connections)
+                    });
+                }
+                (connection1, connection2) => {
+                    self.finish_pairing(
+                        round,
+                        [player1, player2],
+                        Err(None),
+                        [connection1, connection2],
+                    )
+                    .await
+                }
+            }
+        }
+
+        while let Some(played) = pairings_played.join_next().await {
+            match played {
+                Ok((players, result, connections)) => {
+                    self.finish_pairing(round, players, result.map_err(Some), connections)
+                        .await
+                }
+                Err(err) => tracing::error!("TOURNAMENT pairing failed :: {err}"),
+            }
+        }
+
+        for entrant in &mut self.entrants {
+            if entrant.idle.is_none() && !entrant.standing.withdrawn {
+                tracing::info!("TOURNAMENT {} withdrew", entrant.standing.name);
+                entrant.standing.withdrawn = true;
+            }
+        }
+    }
+
+    /// Records the result of a pairing, see [`Tournament::forfeit`] if it
+    /// could not be played out
+    async fn finish_pairing(
+        &mut self,
+        round: usize,
+        [player1, player2]: [usize; 2],
+        result: Result<Vec<Outcome>, Option<crate::stream::Error>>,
+        [connection1, connection2]: [Option<Connection>; 2],
+    ) {
+        match result {
+            Ok(outcomes) => {
+                let wins1 = outcomes
+                    .iter()
+                    .filter(|outcome| outcome.winner == Player::One)
+                    .count() as u32;
+                let wins2 = outcomes.len() as u32 - wins1;
+                self.entrants[player1].standing.match_wins += wins1;
+                self.entrants[player1].standing.match_losses += wins2;
+                self.entrants[player2].standing.match_wins += wins2;
+                self.entrants[player2].standing.match_losses += wins1;
+                if wins1 > wins2 {
+                    self.record(player1, player2);
+                } else {
+                    self.record(player2, player1);
+                }
+
+                if let Some(replays) = &self.config.replays {
+                    let names = [self.name(player1), self.name(player2)];
+                    for (i, outcome) in outcomes.iter().enumerate() {
+                        let file =
+                            format!("round-{round}-{}-vs-{}-{}.txt", names[0], names[1], i + 1);
+                        if let Err(err) =
+                            crate::replay::save(&replays.join(file), names, outcome).await
+                        {
+                            tracing::warn!("TOURNAMENT replay :: {err}");
+                        }
+                    }
+                }
+            }
+            Err(err) => {
+                if let Some(err) = err {
+                    tracing::warn!(
+                        "TOURNAMENT {} vs {} :: {err}",
+                        self.name(player1),
+                        self.name(player2)
+                    );
+                }
+                self.forfeit(
+                    [player1, player2],
+                    [connection1.is_some(), connection2.is_some()],
+                );
+            }
+        }
+
+        for (player, connection) in [(player1, connection1), (player2, connection2)] {
+            self.entrants[player].idle =
+                connection.map(|connection| Idle::new(connection, &self.registry));
+        }
+    }
+
+    /// `None` if the player is not connected
+    async fn take_connection(&mut self, player: usize) -> Option<Connection> {
+        self.entrants[player].idle.take()?.resume().await
+    }
+
+    fn name(&self, player: usize) -> &str {
+        &self.entrants[player].standing.name
+    }
+
+    /// Ends the connections of all players still connected
+    async fn terminate(&mut self) {
+        let mut terminations = task::JoinSet::new();
+        for entrant in &mut self.entrants {
+            let Some(idle) = entrant.idle.take() else {
+                continue;
+            };
+            terminations.spawn(async move {
+                if let Some((stream, _inbox, _login)) = idle.resume().await {
+                    if let Err(err) = stream.request_terminate().await {
+                        tracing::warn!("TOURNAMENT {err}");
+                    }
+                    let _ = stream.close().await;
+                }
+            });
+        }
+        while terminations.join_next().await.is_some() {}
+    }
+
+    fn standings(&self) -> Vec<Standing> {
+        self.ranking()
+            .into_iter()
+            .map(|i| Standing {
+                buchholz: self.buchholz(i),
+                ..self.entrants[i].standing.clone()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Players named `p0`, `p1` and so on, without results
+    fn unplayed(players: usize) -> Tournament {
+        Tournament {
+            entrants: (0..players)
+                .map(|i| Entrant {
+                    standing: Standing {
+                        name: format!("p{i}"),
+                        ..Default::default()
+                    },
+                    opponents: vec![],
+                    idle: None,
+                })
+                .collect(),
+            registry: Arc::new(Mutex::new(crate::players::Registry::default())),
+            config: Config {
+                entrants: vec![],
+                start_after: None,
+                format: Format::RoundRobin,
+                replays: None,
+                series: crate::Config::default(),
+            },
+        }
+    }
+
+    #[test]
+    fn round_robin_odd() {
+        let rounds = round_robin(5);
+        assert_eq!(rounds.len(), 5);
+        let mut met = vec![];
+        for round in rounds {
+            assert_eq!(round.len(), 2);
+            for (player1, player2) in round {
+                let player2 = player2.unwrap();
+                assert_ne!(player1, player2);
+                met.push((player1.min(player2), player1.max(player2)));
+            }
+        }
+        met.sort();
+        let all: Vec<_> = (0..5)
+            .flat_map(|player1| (player1 + 1..5).map(move |player2| (player1, player2)))
+            .collect();
+        assert_eq!(met, all);
+    }
+
+    #[test]
+    fn swiss_bye() {
+        let mut tournament = unplayed(5);
+        for (player, wins) in [3, 2, 1, 0, 0].into_iter().enumerate() {
+            tournament.entrants[player].standing.wins = wins;
+        }
+        assert_eq!(tournament.swiss_pairings()[0], (4, None));
+
+        tournament.entrants[4].standing.byes = 1;
+        assert_eq!(tournament.swiss_pairings()[0], (3, None));
+
+        tournament.entrants[2].standing.withdrawn = true;
+        let pairings = tournament.swiss_pairings();
+        assert_eq!(pairings, [(0, Some(1)), (3, Some(4))]);
+    }
+
+    #[test]
+    fn swiss_no_repeats() {
+        let mut tournament = unplayed(6);
+        // pairing the best two first would leave p2 only opponents it met
+        for opponent in [3, 4, 5] {
+            tournament.entrants[2].opponents.push(opponent);
+            tournament.entrants[opponent].opponents.push(2);
+        }
+        assert_eq!(
+            tournament.swiss_pairings(),
+            [(0, Some(2)), (1, Some(3)), (4, Some(5))]
+        );
+
+        // every pairing repeats, so the ranking decides
+        let mut rematch = unplayed(2);
+        rematch.record(0, 1);
+        assert_eq!(rematch.swiss_pairings(), [(0, Some(1))]);
+    }
+
+    #[tokio::test]
+    async fn swiss_rounds_checked() {
+        for rounds in [0, 3] {
+            let config = Config {
+                entrants: vec!["ada".into(), "bob".into(), "cy".into()],
+                format: Format::Swiss { rounds },
+                ..unplayed(0).config
+            };
+            let addr = (std::net::Ipv4Addr::LOCALHOST, 0).into();
+            assert!(matches!(
+                run(addr, config).await,
+                Err(Error::InvalidRounds(_, 2))
+            ));
+        }
+    }
+
+    #[test]
+    fn forfeits() {
+        let mut tournament = unplayed(2);
+        tournament.forfeit([0, 1], [false, true]);
+        tournament.forfeit([0, 1], [false, false]);
+        assert_eq!(
+            tournament.entrants[0].standing,
+            Standing {
+                name: "p0".into(),
+                losses: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(tournament.entrants[1].standing.wins, 1);
+        assert_eq!(tournament.entrants[1].standing.losses, 0);
+    }
+
+    #[test]
+    fn standings_break_ties() {
+        let mut tournament = unplayed(4);
+        tournament.record(1, 3);
+        tournament.record(0, 2);
+        tournament.record(3, 0);
+        tournament.record(1, 2);
+        let standings: Vec<_> = tournament
+            .standings()
+            .into_iter()
+            .map(|standing| (standing.name, standing.wins, standing.buchholz))
+            .collect();
+        assert_eq!(
+            standings,
+            [
+                ("p1".into(), 2, 1),
+                // ahead of p0 with as many wins, by their stronger opponents
+                ("p3".into(), 1, 3),
+                ("p0".into(), 1, 1),
+                ("p2".into(), 0, 3),
+            ]
+        );
+    }
+}
@@ -1,10 +1,40 @@
 use std::net;
 
-use clap::Parser;
+use clap::{CommandFactory, Parser};
+
+const DEFAULTHISTORY: &str = "ziel-history.log";
+const DEFAULTPLAYERS: &str = "ziel-players.log";
 
 const DEFAULTADDR: net::SocketAddr =
     net::SocketAddr::new(net::IpAddr::V4(net::Ipv4Addr::new(127, 0, 0, 1)), 8080);
 
+/// how the other side of the connection gets checked for signs of life
+#[derive(clap::Args, Debug)]
+struct HeartbeatArgs {
+    /// seconds between two pings
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    heartbeat_interval: u64,
+    /// seconds without an answer, after which the connection counts as dead,
+    /// longer than the interval
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u64).range(1..))]
+    heartbeat_timeout: u64,
+}
+
+impl HeartbeatArgs {
+    /// Exits with a usage error, unless the timeout is longer than the interval
+    fn heartbeat(self) -> protocol::session::Heartbeat {
+        protocol::session::Heartbeat::new(
+            std::time::Duration::from_secs(self.heartbeat_interval),
+            std::time::Duration::from_secs(self.heartbeat_timeout),
+        )
+        .unwrap_or_else(|err| {
+            Args::command()
+                .error(clap::error::ErrorKind::ArgumentConflict, err)
+                .exit()
+        })
+    }
+}
+
 /// ziel - battleship
 #[derive(clap::Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -14,36 +44,500 @@ enum Args {
         /// where to listen for clients
         #[arg(short, long, default_value_t = DEFAULTADDR)]
         addr: std::net::SocketAddr,
+        /// play a series over this many matches, instead of unlimited rematches
+        #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..))]
+        best_of: Option<u8>,
+        /// let the other player shoot first in every rematch
+        #[arg(short, long)]
+        swap_first: bool,
+        /// file the finished matches get recorded in
+        #[arg(long, default_value = DEFAULTHISTORY)]
+        history: std::path::PathBuf,
+        /// do not record the matches
+        #[arg(long)]
+        no_history: bool,
+        /// file the names, passwords and ratings of the players are kept in
+        #[arg(long, default_value = DEFAULTPLAYERS)]
+        players: std::path::PathBuf,
+        /// seconds a player waits for an opponent, before the computer plays against them
+        #[arg(long)]
+        computer_after: Option<u64>,
+        /// strategy the computer plays with
+        #[arg(long, default_value = "hunt", value_parser = clap::builder::PossibleValuesParser::new(ai::STRATEGIES))]
+        computer_strategy: String,
+        #[command(flatten)]
+        heartbeat: HeartbeatArgs,
     },
     /// join a server
     Client {
         /// where to bind for the game server
         #[arg(short, long, default_value_t = DEFAULTADDR)]
         addr: std::net::SocketAddr,
+        /// the name other players see, letters, digits, '-' and '_'
+        #[arg(short, long)]
+        name: String,
+        /// protects the name, the first password used with a name claims it
+        #[arg(short, long)]
+        password: Option<String>,
+        #[command(flatten)]
+        heartbeat: HeartbeatArgs,
     },
+    /// join a server with a computer player, without a terminal UI
+    Bot {
+        /// where to bind for the game server
+        #[arg(short, long, default_value_t = DEFAULTADDR)]
+        addr: std::net::SocketAddr,
+        /// the name other players see, letters, digits, '-' and '_'
+        #[arg(short, long, default_value = "bot")]
+        name: String,
+        /// protects the name, the first password used with a name claims it
+        #[arg(short, long)]
+        password: Option<String>,
+        /// computer strategy to play with
+        #[arg(short, long, default_value = "hunt", value_parser = clap::builder::PossibleValuesParser::new(ai::STRATEGIES))]
+        strategy: String,
+        /// bot executable speaking the engine protocol, played instead of a strategy
+        #[arg(short, long, conflicts_with = "strategy")]
+        engine: Option<std::path::PathBuf>,
+        /// seconds the engine may take to answer
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+        engine_timeout: u64,
+        /// number of matches, played one after another
+        #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        games: u32,
+        /// seed of the random choices of the strategy
+        #[arg(long)]
+        seed: Option<u64>,
+        #[command(flatten)]
+        heartbeat: HeartbeatArgs,
+    },
+    /// host a tournament between the named players
+    Tournament {
+        /// where to listen for clients
+        #[arg(short, long, default_value_t = DEFAULTADDR)]
+        addr: std::net::SocketAddr,
+        /// a player taking part, repeat for every player
+        #[arg(short = 'n', long = "player", value_name = "NAME", required = true)]
+        entrants: Vec<String>,
+        /// seconds to wait for all players, before starting without the missing ones
+        #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+        start_after: Option<u64>,
+        /// pair the players in swiss rounds, instead of everyone against everyone
+        #[arg(long)]
+        swiss: bool,
+        /// number of swiss rounds, fewer than the players, by default enough to
+        /// leave one player unbeaten
+        #[arg(long, requires = "swiss")]
+        rounds: Option<usize>,
+        /// play every pairing over this many matches, instead of a single one
+        #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..))]
+        best_of: Option<u8>,
+        /// let the other player shoot first in every match of a pairing
+        #[arg(short, long)]
+        swap_first: bool,
+        /// directory a replay of every match gets written to
+        #[arg(long)]
+        replays: Option<std::path::PathBuf>,
+        /// file the finished matches get recorded in
+        #[arg(long, default_value = DEFAULTHISTORY)]
+        history: std::path::PathBuf,
+        /// do not record the matches
+        #[arg(long)]
+        no_history: bool,
+        /// file the names, passwords and ratings of the players are kept in
+        #[arg(long, default_value = DEFAULTPLAYERS)]
+        players: std::path::PathBuf,
+        #[command(flatten)]
+        heartbeat: HeartbeatArgs,
+    },
+    /// show player statistics of the recorded matches
+    Stats {
+        /// file the server recorded the matches in
+        #[arg(long, default_value = DEFAULTHISTORY)]
+        history: std::path::PathBuf,
+        /// only show this player
+        #[arg(short, long)]
+        player: Option<String>,
+    },
+    /// let two computer strategies play each other and compare them
+    Simulate {
+        /// strategy of the first player
+        #[arg(value_parser = clap::builder::PossibleValuesParser::new(ai::STRATEGIES))]
+        strategy1: String,
+        /// strategy of the second player
+        #[arg(value_parser = clap::builder::PossibleValuesParser::new(ai::STRATEGIES))]
+        strategy2: String,
+        /// number of matches, the first shot alternates between the players
+        #[arg(short, long, default_value_t = 1000, value_parser = clap::value_parser!(u32).range(1..))]
+        games: u32,
+        /// seed of the random choices, a run with the same seed plays the same matches
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// run a computer strategy as bot, speaking the engine protocol on stdin and stdout
+    Engine {
+        #[arg(value_parser = clap::builder::PossibleValuesParser::new(ai::STRATEGIES))]
+        strategy: String,
+        /// seed of the random choices
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+}
+
+/// A seed differing between runs, for when none was given
+fn time_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64)
 }
 
-async fn run_server(addr: net::SocketAddr) {
+async fn run_server(addr: net::SocketAddr, config: server::Config) {
     tracing_subscriber::fmt().with_thread_ids(true).init();
-    match server::listen(addr).await {
+    match server::listen(addr, config).await {
         Ok(()) => {}
         Err(err) => tracing::error!("{err}"),
     }
 }
 
-async fn run_tui_client(addr: net::SocketAddr) -> Result<bool, client::Error<tui::Tui>> {
+async fn run_tournament(addr: net::SocketAddr, config: server::tournament::Config) {
+    tracing_subscriber::fmt().with_thread_ids(true).init();
+    let standings = match server::tournament::run(addr, config).await {
+        Ok(standings) => standings,
+        Err(err) => return tracing::error!("{err}"),
+    };
+
+    println!(
+        "{:>4} {:<16} {:>4} {:>6} {:>4} {:>7} {:>8}",
+        "rank", "player", "wins", "losses", "byes", "matches", "buchholz"
+    );
+    for (rank, standing) in standings.into_iter().enumerate() {
+        println!(
+            "{:>4} {:<16} {:>4} {:>6} {:>4} {:>7} {:>8}{}",
+            rank + 1,
+            standing.name,
+            standing.wins,
+            standing.losses,
+            standing.byes,
+            format!("{}-{}", standing.match_wins, standing.match_losses),
+            standing.buchholz,
+            if standing.withdrawn { " withdrawn" } else { "" },
+        );
+    }
+}
+
+/// Checks the name and password, before connecting with them
+fn identity(name: String, password: Option<String>) -> Option<client::Identity> {
+    if !protocol::is_valid_name(&name) {
+        eprintln!(
+            "invalid name, use 1 to {} letters, digits, '-' and '_'",
+            protocol::MAX_NAME_LEN
+        );
+        return None;
+    }
+    if password
+        .as_ref()
+        .is_some_and(|password| password.len() > protocol::MAX_PASSWORD_LEN)
+    {
+        eprintln!(
+            "password too long, use at most {} bytes",
+            protocol::MAX_PASSWORD_LEN
+        );
+        return None;
+    }
+    Some(client::Identity { name, password })
+}
+
+/// Plays `games` rounds one after another, reporting every one and a summary
+async fn run_bot<I: client::UI>(
+    ui: &mut I,
+    addr: net::SocketAddr,
+    config: client::Config,
+    games: u32,
+) {
+    let (mut won, mut lost, mut failed) = (0, 0, 0);
+    let mut played = std::time::Duration::ZERO;
+    for game in 1..=games {
+        let start = std::time::Instant::now();
+        match client::play_round(ui, addr, &config).await {
+            Ok(victory) => {
+                let duration = start.elapsed();
+                played += duration;
+                if victory {
+                    won += 1;
+                } else {
+                    lost += 1;
+                }
+                println!(
+                    "game {game}: {} ({:.1}s)",
+                    if victory { "won" } else { "lost" },
+                    duration.as_secs_f64()
+                );
+            }
+            Err(err) => {
+                failed += 1;
+                println!("game {game}: failed :: {err}");
+            }
+        }
+    }
+
+    print!("{games} games: {won} won, {lost} lost, {failed} failed");
+    match won + lost {
+        0 => println!(),
+        finished => println!(
+            ", {:.1}% won, {:.1}s per game",
+            won as f64 / finished as f64 * 100.0,
+            played.as_secs_f64() / finished as f64
+        ),
+    }
+}
+
+async fn run_tui_client(
+    addr: net::SocketAddr,
+    config: client::Config,
+) -> Result<bool, client::Error<tui::Tui>> {
     let mut tui = tui::Tui::init();
-    client::play_round(&mut tui, addr).await
+    client::play_round(&mut tui, addr, &config).await
+}
+
+fn print_stats(
+    history: &std::path::Path,
+    player: Option<String>,
+) -> Result<(), server::history::Error> {
+    let records = server::history::load(history)?;
+    let stats: Vec<_> = server::history::stats(&records)
+        .into_iter()
+        .filter(|stats| player.as_ref().is_none_or(|player| &stats.name == player))
+        .collect();
+    if stats.is_empty() {
+        println!("no matches recorded");
+        return Ok(());
+    }
+
+    let percent =
+        |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.1}%", v * 100.0));
+    println!(
+        "{:<24} {:>7} {:>5} {:>8} {:>8} {:>13} {:>6} {:>11}",
+        "player",
+        "matches",
+        "wins",
+        "win rate",
+        "accuracy",
+        "shots to win",
+        "streak",
+        "best streak"
+    );
+    for stats in stats {
+        let streak = match stats.streak {
+            streak if streak > 0 => format!("W{streak}"),
+            streak if streak < 0 => format!("L{}", -streak),
+            _ => "-".to_string(),
+        };
+        println!(
+            "{:<24} {:>7} {:>5} {:>8} {:>8} {:>13} {:>6} {:>11}",
+            stats.name,
+            stats.matches,
+            stats.wins,
+            percent(Some(stats.win_rate())),
+            percent(stats.accuracy()),
+            stats
+                .average_shots_to_win()
+                .map_or("-".to_string(), |shots| format!("{shots:.1}")),
+            streak,
+            stats.longest_win_streak,
+        );
+    }
+    Ok(())
+}
+
+fn print_simulation(report: ai::simulate::Report) {
+    println!("{} matches, seed {}", report.games, report.seed);
+    println!(
+        "{:<8} {:>5} {:>17} {:>19} {:>6} {:>4} {:>4} {:>4}",
+        "strategy", "wins", "win rate (95%)", "mean shots (95%)", "median", "p10", "p90", "p99"
+    );
+    let shots = |shots: Option<u16>| shots.map_or("-".to_string(), |shots| shots.to_string());
+    for player in report.players {
+        let (low, high) = player.win_rate_interval();
+        println!(
+            "{:<8} {:>5} {:>17} {:>19} {:>6} {:>4} {:>4} {:>4}",
+            player.name,
+            player.wins,
+            format!(
+                "{:.1}% {:.1}-{:.1}",
+                player.win_rate() * 100.0,
+                low * 100.0,
+                high * 100.0
+            ),
+            match (
+                player.mean_shots_to_win(),
+                player.mean_shots_to_win_interval()
+            ) {
+                (Some(mean), Some((low, high))) => format!("{mean:.1} {low:.1}-{high:.1}"),
+                (Some(mean), None) => format!("{mean:.1}"),
+                _ => "-".to_string(),
+            },
+            shots(player.percentile(50.0)),
+            shots(player.percentile(10.0)),
+            shots(player.percentile(90.0)),
+            shots(player.percentile(99.0)),
+        );
+    }
 }
 
 #[tokio::main]
 async fn main() {
     match Args::parse() {
-        Args::Server { addr } => run_server(addr).await,
-        Args::Client { addr } => match run_tui_client(addr).await {
-            Ok(true) => println!("congrats, you won"),
-            Ok(false) => println!("you lost, maybe try again?"),
-            Err(err) => eprintln!("{err}"),
-        },
+        Args::Server {
+            addr,
+            best_of,
+            swap_first,
+            history,
+            no_history,
+            players,
+            computer_after,
+            computer_strategy,
+            heartbeat,
+        } => {
+            run_server(
+                addr,
+                server::Config {
+                    best_of,
+                    swap_first,
+                    heartbeat: heartbeat.heartbeat(),
+                    history: (!no_history).then_some(history),
+                    players: Some(players),
+                    computer: computer_after.map(|wait| server::computer::Config {
+                        wait: std::time::Duration::from_secs(wait),
+                        strategy: computer_strategy,
+                    }),
+                },
+            )
+            .await
+        }
+        Args::Tournament {
+            addr,
+            entrants,
+            start_after,
+            swiss,
+            rounds,
+            best_of,
+            swap_first,
+            replays,
+            history,
+            no_history,
+            players,
+            heartbeat,
+        } => {
+            let format = match swiss {
+                true => server::tournament::Format::Swiss {
+                    rounds: rounds
+                        .unwrap_or(server::tournament::Format::swiss_rounds(entrants.len())),
+                },
+                false => server::tournament::Format::RoundRobin,
+            };
+            run_tournament(
+                addr,
+                server::tournament::Config {
+                    entrants,
+                    start_after: start_after.map(std::time::Duration::from_secs),
+                    format,
+                    replays,
+                    series: server::Config {
+                        best_of,
+                        swap_first,
+                        heartbeat: heartbeat.heartbeat(),
+                        history: (!no_history).then_some(history),
+                        players: Some(players),
+                        computer: None,
+                    },
+                },
+            )
+            .await
+        }
+        Args::Client {
+            addr,
+            name,
+            password,
+            heartbeat,
+        } => {
+            let Some(identity) = identity(name, password) else {
+                return;
+            };
+            let config = client::Config {
+                identity,
+                heartbeat: heartbeat.heartbeat(),
+            };
+            match run_tui_client(addr, config).await {
+                Ok(true) => println!("congrats, you won"),
+                Ok(false) => println!("you lost, maybe try again?"),
+                Err(err) => eprintln!("{err}"),
+            }
+        }
+        Args::Bot {
+            addr,
+            name,
+            password,
+            strategy,
+            engine,
+            engine_timeout,
+            games,
+            seed,
+            heartbeat,
+        } => {
+            let Some(identity) = identity(name, password) else {
+                return;
+            };
+            let config = client::Config {
+                identity,
+                heartbeat: heartbeat.heartbeat(),
+            };
+            match engine {
+                Some(engine) => {
+                    let timeout = std::time::Duration::from_secs(engine_timeout);
+                    match ai::Engine::spawn(&mut tokio::process::Command::new(engine), timeout)
+                        .await
+                    {
+                        Ok(mut engine) => {
+                            run_bot(&mut engine, addr, config, games).await;
+                            engine.close().await
+                        }
+                        Err(err) => eprintln!("{err}"),
+                    }
+                }
+                None => {
+                    let strategy = ai::by_name(&strategy, seed.unwrap_or_else(time_seed))
+                        .expect("strategy names are checked by clap");
+                    run_bot(&mut ai::Bot::new(strategy), addr, config, games).await
+                }
+            }
+        }
+        Args::Stats { history, player } => {
+            tracing_subscriber::fmt()
+                .with_writer(std::io::stderr)
+                .init();
+            if let Err(err) = print_stats(&history, player) {
+                eprintln!("{err}")
+            }
+        }
+        Args::Simulate {
+            strategy1,
+            strategy2,
+            games,
+            seed,
+        } => {
+            let seed = seed.unwrap_or_else(time_seed);
+            match ai::simulate::run([&strategy1, &strategy2], games, seed) {
+                Ok(report) => print_simulation(report),
+                Err(err) => eprintln!("{err}"),
+            }
+        }
+        Args::Engine { strategy, seed } => {
+            let mut strategy = ai::by_name(&strategy, seed.unwrap_or_else(time_seed))
+                .expect("strategy names are checked by clap");
+            let input = tokio::io::BufReader::new(tokio::io::stdin());
+            if let Err(err) = ai::engine::serve(&mut strategy, input, tokio::io::stdout()).await {
+                eprintln!("{err}")
+            }
+        }
     }
 }
@@ -1,7 +1,24 @@
 use ratatui::{style, widgets::canvas};
 
+/// Paints the positions of a board as squares of `scale` half blocks
+pub struct Cells<'a, 'b> {
+    painter: canvas::Painter<'a, 'b>,
+    scale: usize,
+}
+
+impl Cells<'_, '_> {
+    pub fn paint(&mut self, x: usize, y: usize, color: style::Color) {
+        for dy in 0..self.scale {
+            for dx in 0..self.scale {
+                self.painter
+                    .paint(x * self.scale + dx, y * self.scale + dy, color);
+            }
+        }
+    }
+}
+
 pub trait ShapeExt {
-    fn draw_ext(self, painter: &mut canvas::Painter);
+    fn draw_ext(self, cells: &mut Cells);
 }
 
 pub trait ContextExt {
@@ -15,43 +32,71 @@ pub trait ContextExt {
 
 impl<'a> ContextExt for canvas::Context<'a> {
     fn draw_ext<S: ShapeExt>(&mut self, shape: S) {
-        shape.draw_ext(&mut canvas::Painter::from(self));
+        Scaled::new(self, 1).draw_ext(shape);
+    }
+    fn draw_ext_batch<S: ShapeExt, I: Iterator<Item = S>>(&mut self, iter: I) {
+        Scaled::new(self, 1).draw_ext_batch(iter);
+    }
+}
+
+/// A canvas context, on which every position of the board is `scale` half
+/// blocks wide and high
+pub struct Scaled<'c, 'a> {
+    ctx: &'c mut canvas::Context<'a>,
+    scale: usize,
+}
+
+impl<'c, 'a> Scaled<'c, 'a> {
+    pub fn new(ctx: &'c mut canvas::Context<'a>, scale: u16) -> Scaled<'c, 'a> {
+        Scaled {
+            ctx,
+            scale: scale as usize,
+        }
+    }
+}
+
+impl ContextExt for Scaled<'_, '_> {
+    fn draw_ext<S: ShapeExt>(&mut self, shape: S) {
+        self.draw_ext_batch(std::iter::once(shape));
     }
     fn draw_ext_batch<S: ShapeExt, I: Iterator<Item = S>>(&mut self, iter: I) {
-        let mut painter = canvas::Painter::from(self);
+        let mut cells = Cells {
+            painter: canvas::Painter::from(&mut *self.ctx),
+            scale: self.scale,
+        };
         for item in iter {
-            item.draw_ext(&mut painter);
+            item.draw_ext(&mut cells);
         }
     }
 }
 
 impl ShapeExt for logic::ship::Ship {
-    fn draw_ext(self, painter: &mut canvas::Painter) {
+    fn draw_ext(self, cells: &mut Cells) {
         for pos in self {
             let (x, y) = pos.to_coords();
-            painter.paint(x as usize, y as usize, style::Color::White);
+            cells.paint(x as usize, y as usize, style::Color::White);
         }
     }
 }
 
 impl ShapeExt for (logic::ship::Ship, style::Color) {
-    fn draw_ext(self, painter: &mut canvas::Painter) {
+    fn draw_ext(self, cells: &mut Cells) {
         for pos in self.0 {
             let (x, y) = pos.to_coords();
-            painter.paint(x as usize, y as usize, self.1);
+            cells.paint(x as usize, y as usize, self.1);
         }
     }
 }
 
 impl ShapeExt for &[[Option<client::AttackInfo>; 10]; 10] {
-    fn draw_ext(self, painter: &mut canvas::Painter) {
+    fn draw_ext(self, cells: &mut Cells) {
         for ((x, y), info) in self
             .into_iter()
             .enumerate()
             .flat_map(|(i, info)| Iterator::zip(std::iter::repeat(i), info.into_iter().enumerate()))
             .filter_map(|(y, (x, info))| info.map(|i| ((x, y), i)))
         {
-            painter.paint(
+            cells.paint(
                 x,
                 y,
                 match info {
@@ -63,9 +108,27 @@ impl ShapeExt for &[[Option<client::AttackInfo>; 10]; 10] {
     }
 }
 
+/// the density of the advisor, the likeliest positions drawn brightest
+impl ShapeExt for &[[u32; 10]; 10] {
+    fn draw_ext(self, cells: &mut Cells) {
+        let max = self.iter().flatten().copied().max().unwrap_or(0).max(1);
+        for (y, row) in self.iter().enumerate() {
+            for (x, &density) in row.iter().enumerate().filter(|(_, &d)| d > 0) {
+                let heat = density as f64 / max as f64;
+                let color = style::Color::Rgb(
+                    (60.0 + 195.0 * heat) as u8,
+                    (40.0 + 120.0 * heat) as u8,
+                    (100.0 * (1.0 - heat)) as u8,
+                );
+                cells.paint(x, y, color);
+            }
+        }
+    }
+}
+
 impl ShapeExt for (logic::Position, style::Color) {
-    fn draw_ext(self, painter: &mut canvas::Painter) {
+    fn draw_ext(self, cells: &mut Cells) {
         let (x, y) = self.0.to_coords();
-        painter.paint(x as usize, y as usize, self.1);
+        cells.paint(x as usize, y as usize, self.1);
     }
 }
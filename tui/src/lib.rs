@@ -1,6 +1,7 @@
 use std::io;
 
-use crossterm::event::{self, KeyCode};
+use crossterm::event::{self, KeyCode, MouseButton, MouseEventKind};
+use futures::StreamExt;
 use ratatui::{
     layout,
     style::{self, Stylize},
@@ -25,13 +26,23 @@ struct Layout<'s> {
     pub opponent_board: layout::Rect,
     pub messages: layout::Rect,
     pub help: layout::Rect,
+    /// Left of the boards, empty on narrow terminals
+    pub fleet: layout::Rect,
+    /// Half blocks per position of a board, `1` for the compact boards
+    pub scale: u16,
+    /// Above the boards, empty for the compact boards
+    pub column_labels: layout::Rect,
+    /// Left of the client's board, empty for the compact boards
+    pub client_row_labels: layout::Rect,
+    /// Right of the opponent's board, empty for the compact boards
+    pub opponent_row_labels: layout::Rect,
 
     pub client_board_border: widgets::Block<'s>,
     pub opponent_board_border: widgets::Block<'s>,
 }
 
-fn message_to_line(message: client::ui::Message) -> Option<text::Line<'static>> {
-    match message {
+fn message_to_line(message: &client::ui::Message) -> Option<text::Line<'static>> {
+    match *message {
         client::ui::Message::OpponentSelectsTarget => None,
         client::ui::Message::ClientMissedOpponent(pos) => Some(text::Line::from(vec![
             text::Span::raw("your shot "),
@@ -63,30 +74,177 @@ fn message_to_line(message: client::ui::Message) -> Option<text::Line<'static>>
             text::Span::raw("sunk").light_red(),
             text::Span::raw(format!("  len: {length:>2}")),
         ])),
+        client::ui::Message::ClientChat(ref chat) => Some(text::Line::from(vec![
+            text::Span::raw("you: ").yellow(),
+            text::Span::raw(chat.clone()).white(),
+        ])),
+        client::ui::Message::OpponentChat(ref chat) => Some(text::Line::from(vec![
+            text::Span::raw("opp: ").light_red(),
+            text::Span::raw(chat.clone()).white(),
+        ])),
+    }
+}
+
+/// Width of the fleet panel, the longest ship and a gap to the board
+const FLEET_WIDTH: u16 = 7;
+
+/// Largest scale of the boards
+const MAX_SCALE: u16 = 4;
+
+/// Lines at least left to the help area and the messages each, before the
+/// boards are scaled up
+const PANEL_HEIGHT: u16 = 8;
+
+/// Width of the row numbers next to a scaled board
+const LABEL_WIDTH: u16 = 2;
+
+const COLUMN_LABELS: [char; 10] = ['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J'];
+
+/// Intact parts in the color of the ship, hit parts as crosses
+fn ship_status_to_line(index: usize, status: &client::ui::ShipStatus) -> text::Line<'static> {
+    let intact = status.length().saturating_sub(status.hits) as usize;
+    let (color, hit_color) = match status.sunk {
+        true => (style::Color::DarkGray, style::Color::DarkGray),
+        false => (SHIP_COLORS[index], style::Color::LightRed),
+    };
+    text::Line::from(vec![
+        text::Span::raw("■".repeat(intact)).fg(color),
+        text::Span::raw("×".repeat(status.hits as usize)).fg(hit_color),
+        text::Span::raw("  "),
+    ])
+    .right_aligned()
+}
+
+fn score_to_line(score: client::ui::Score) -> text::Line<'static> {
+    let mut spans = vec![
+        text::Span::raw("score "),
+        text::Span::raw(format!("{}", score.client)).yellow(),
+        text::Span::raw(" - "),
+        text::Span::raw(format!("{}", score.opponent)).light_red(),
+    ];
+    if let Some(best_of) = score.best_of {
+        spans.push(text::Span::raw(format!("  bo{best_of}")));
+    }
+    text::Line::from(spans)
+}
+
+/// Silence after which the connection is shown as troubled
+const SILENCE_WARNING: std::time::Duration = std::time::Duration::from_secs(10);
+
+fn connection_to_line(health: client::ui::Health) -> text::Line<'static> {
+    let silent = health.last_seen.elapsed();
+    if silent >= SILENCE_WARNING {
+        return text::Line::from(format!("no answer for {}s", silent.as_secs())).light_red();
+    }
+    match health.latency {
+        Some(latency) => text::Line::from(vec![
+            text::Span::raw("ping "),
+            text::Span::raw(format!("{}ms", latency.as_millis())).white(),
+        ]),
+        None => text::Line::raw("ping -"),
+    }
+}
+
+fn queue_to_line(status: client::ui::QueueStatus) -> text::Line<'static> {
+    let wait = match status.estimated_wait {
+        Some(wait) => format!("~{wait}s"),
+        None => "?".to_string(),
+    };
+    text::Line::from(vec![
+        text::Span::raw("waiting for opponent "),
+        text::Span::raw(format!("#{}", status.position)).white(),
+        text::Span::raw(" est. "),
+        text::Span::raw(wait).white(),
+    ])
+}
+
+fn standing_to_line(
+    rank: usize,
+    standing: &client::ui::Standing,
+    own: bool,
+) -> text::Line<'static> {
+    let line = text::Line::from(vec![
+        text::Span::raw(format!("{:>2} ", rank + 1)),
+        text::Span::raw(format!("{:<16}", standing.name)),
+        text::Span::raw(format!("{:>5}", standing.rating)).white(),
+        text::Span::raw(format!(" {:>3}-{:<3}", standing.wins, standing.losses)),
+    ]);
+    if own {
+        line.yellow()
+    } else {
+        line
+    }
+}
+
+fn surrender_prompt() -> Vec<text::Line<'static>> {
+    vec![text::Line::from(vec![
+        text::Span::raw("surrender? "),
+        text::Span::raw("y").bold(),
+        text::Span::raw("/"),
+        text::Span::raw("n").bold(),
+    ])
+    .light_red()]
+}
+
+/// Where a board was drawn last, to map the mouse back to its positions
+#[derive(Debug, Clone, Copy)]
+struct BoardArea {
+    /// Inside the border of the board
+    inner: layout::Rect,
+    scale: u16,
+}
+
+impl BoardArea {
+    /// The position under the terminal cell. A cell of a compact board shows
+    /// two positions, the one nearer to the cursor is taken.
+    fn position(&self, column: u16, row: u16, cursor: (u8, u8)) -> Option<logic::Position> {
+        if !self.inner.contains(layout::Position { x: column, y: row }) {
+            return None;
+        }
+        let x = (column - self.inner.x) / self.scale;
+        // a terminal cell is two half blocks high
+        let half_blocks = (row - self.inner.y) * 2;
+        let y = (cursor.1 as u16).clamp(half_blocks / self.scale, (half_blocks + 1) / self.scale);
+        logic::Position::try_from_coords((x as u8, y as u8)).ok()
     }
 }
 
 impl<'s> Layout<'s> {
     fn generate(area: layout::Rect) -> Layout<'s> {
-        let [_left, middle, _right] = layout::Layout::horizontal([
+        let scale = Layout::scale(area);
+        let size = Layout::boards_size(scale);
+        let [left, middle, _right] = layout::Layout::horizontal([
             layout::Constraint::Fill(1),
-            layout::Constraint::Length(23),
+            layout::Constraint::Length(size.width),
             layout::Constraint::Fill(1),
         ])
         .areas(area);
 
-        let [help, boards, messages] = layout::Layout::vertical([
+        let rows = layout::Layout::vertical([
             layout::Constraint::Fill(1),
-            layout::Constraint::Length(7),
+            layout::Constraint::Length(size.height),
+            layout::Constraint::Fill(1),
+        ]);
+        let [help, boards, messages] = rows.areas(middle);
+        let [_, fleet, _] = rows.areas(left);
+        let [fleet] = layout::Layout::horizontal([layout::Constraint::Length(FLEET_WIDTH)])
+            .flex(layout::Flex::End)
+            .areas(fleet);
+
+        let labels = if scale > 1 { 1 } else { 0 };
+        let [column_labels, boards] = layout::Layout::vertical([
+            layout::Constraint::Length(labels),
             layout::Constraint::Fill(1),
-        ])
-        .areas(middle);
-
-        let [client_board, opponent_board] = layout::Layout::horizontal([
-            layout::Constraint::Length(11),
-            layout::Constraint::Length(12),
         ])
         .areas(boards);
+        let [client_row_labels, client_board, opponent_board, opponent_row_labels] =
+            layout::Layout::horizontal([
+                layout::Constraint::Length(labels * LABEL_WIDTH),
+                layout::Constraint::Length(10 * scale + 1),
+                layout::Constraint::Length(10 * scale + 2),
+                layout::Constraint::Length(labels * LABEL_WIDTH),
+            ])
+            .areas(boards);
 
         let client_board_border = widgets::Block::bordered()
             .border_type(widgets::BorderType::Thick)
@@ -105,6 +263,11 @@ impl<'s> Layout<'s> {
             opponent_board,
             messages,
             help,
+            fleet,
+            scale,
+            column_labels,
+            client_row_labels,
+            opponent_row_labels,
             client_board_border,
             opponent_board_border,
         }
@@ -112,7 +275,7 @@ impl<'s> Layout<'s> {
 
     fn paint_client_board<F>(&self, f: &mut ratatui::Frame, paint_fn: F)
     where
-        F: Fn(&mut canvas::Context),
+        F: Fn(&mut draw_ext::Scaled),
     {
         f.render_widget(
             Tui::new_board_canvas(self.client_board_border.clone()).paint(|ctx| {
@@ -120,15 +283,16 @@ impl<'s> Layout<'s> {
                     coords: &[(0.0, 0.0)],
                     color: style::Color::White,
                 });
-                paint_fn(ctx);
+                paint_fn(&mut draw_ext::Scaled::new(ctx, self.scale));
             }),
             self.client_board,
         );
+        self.draw_labels(f, self.client_board, self.client_row_labels);
     }
 
     fn paint_opponent_board<F>(&self, f: &mut ratatui::Frame, paint_fn: F)
     where
-        F: Fn(&mut canvas::Context),
+        F: Fn(&mut draw_ext::Scaled),
     {
         f.render_widget(
             Tui::new_board_canvas(self.opponent_board_border.clone()).paint(|ctx| {
@@ -136,21 +300,212 @@ impl<'s> Layout<'s> {
                     coords: &[(0.0, 0.0)],
                     color: style::Color::White,
                 });
-                paint_fn(ctx);
+                paint_fn(&mut draw_ext::Scaled::new(ctx, self.scale));
             }),
             self.opponent_board,
         );
+        self.draw_labels(f, self.opponent_board, self.opponent_row_labels);
+    }
+
+    /// Draws the column letters above and the row numbers next to a scaled
+    /// board, so positions like `B7` can be read off it
+    fn draw_labels(&self, f: &mut ratatui::Frame, board: layout::Rect, rows: layout::Rect) {
+        if self.scale == 1 {
+            return;
+        }
+        let width = self.scale as usize;
+        let columns: String = COLUMN_LABELS
+            .iter()
+            .map(|label| format!("{label:^width$}"))
+            .collect();
+        let area = layout::Rect {
+            x: board.x + 1,
+            width: 10 * self.scale,
+            ..self.column_labels
+        };
+        f.render_widget(widgets::Paragraph::new(columns).dark_gray(), area);
+
+        // a position is half as many lines high as it is wide
+        let height = self.scale as usize / 2;
+        let lines: Vec<_> = (1..=10)
+            .flat_map(|row| {
+                let mut lines = vec![text::Line::raw(""); height];
+                lines[(height - 1) / 2] = text::Line::raw(format!("{row}"));
+                lines
+            })
+            .collect();
+        let area = layout::Rect {
+            y: board.y + 1,
+            height: 5 * self.scale,
+            ..rows
+        };
+        let labels = widgets::Paragraph::new(lines).dark_gray();
+        f.render_widget(
+            if rows.x < board.x {
+                labels.right_aligned()
+            } else {
+                labels.left_aligned()
+            },
+            area,
+        );
     }
 
-    fn draw_messages(&self, f: &mut ratatui::Frame, messages: &[client::ui::Message]) {
+    /// Draws the messages, newest first, below the chat input line if the
+    /// player is typing
+    fn draw_messages(
+        &self,
+        f: &mut ratatui::Frame,
+        messages: &[client::ui::Message],
+        chat_input: Option<&str>,
+    ) {
+        let mut area = self.messages;
+        if let Some(input) = chat_input {
+            let [input_area, messages_area] = layout::Layout::vertical([
+                layout::Constraint::Length(1),
+                layout::Constraint::Fill(1),
+            ])
+            .areas(area);
+            area = messages_area;
+
+            // only the end of the input fits, next to the prompt and cursor
+            let visible = input_area.width.saturating_sub(3) as usize;
+            let skip = input.chars().count().saturating_sub(visible);
+            f.render_widget(
+                widgets::Paragraph::new(text::Line::from(vec![
+                    text::Span::raw("> ").yellow(),
+                    text::Span::raw(input.chars().skip(skip).collect::<String>()),
+                    text::Span::raw("_").slow_blink(),
+                ])),
+                input_area,
+            );
+        }
+
         let iter: Vec<_> = messages
             .into_iter()
             .rev()
             .into_iter()
-            .filter_map(|&m| message_to_line(m))
+            .filter_map(message_to_line)
+            .collect();
+
+        f.render_widget(
+            widgets::Paragraph::new(iter)
+                .wrap(widgets::Wrap { trim: true })
+                .gray(),
+            area,
+        );
+    }
+
+    /// Draws the damage of every ship of the player, next to their board
+    fn draw_fleet(&self, f: &mut ratatui::Frame, info: client::ui::ClientInfo) {
+        let lines: Vec<_> = info
+            .fleet
+            .iter()
+            .enumerate()
+            .map(|(i, status)| ship_status_to_line(i, status))
             .collect();
+        let [area] = layout::Layout::vertical([layout::Constraint::Length(lines.len() as u16)])
+            .flex(layout::Flex::Center)
+            .areas(self.fleet);
+        f.render_widget(widgets::Paragraph::new(lines), area);
+    }
+
+    /// Draws the opponent's fleet as checklist in the help area, sunk ships
+    /// are checked
+    fn draw_opponent_fleet(&self, f: &mut ratatui::Frame, info: client::ui::ClientInfo) {
+        let [_, area, _] = layout::Layout::vertical([
+            layout::Constraint::Length(1),
+            layout::Constraint::Fill(1),
+            layout::Constraint::Length(2),
+        ])
+        .areas(self.help);
+        let lines: Vec<_> = info
+            .opponent_fleet()
+            .into_iter()
+            .map(|(length, sunk)| {
+                let ship = format!("{:<5}", "■".repeat(length as usize));
+                match sunk {
+                    true => text::Line::raw(format!("[x] {ship}")).dark_gray(),
+                    false => text::Line::from(vec![
+                        text::Span::raw("[ ] "),
+                        text::Span::raw(ship).light_red(),
+                    ]),
+                }
+            })
+            .collect();
+        let [area] = layout::Layout::vertical([layout::Constraint::Length(lines.len() as u16)])
+            .flex(layout::Flex::Center)
+            .areas(area);
+        f.render_widget(widgets::Paragraph::new(lines).centered().gray(), area);
+    }
+
+    /// Draws the connection status and turn at the top of the help area
+    fn draw_status(&self, f: &mut ratatui::Frame, info: client::ui::ClientInfo) {
+        let [area] = layout::Layout::vertical([layout::Constraint::Length(1)]).areas(self.help);
+
+        let line = match info.queue {
+            Some(status) => queue_to_line(status),
+            None => {
+                let mut line = connection_to_line(info.connection.health());
+                if let Some(turn) = info.history.last() {
+                    line.push_span(text::Span::raw(format!("  turn {}", turn.number)));
+                }
+                line
+            }
+        };
+        f.render_widget(widgets::Paragraph::new(line).centered().gray(), area);
+    }
+
+    /// Draws the leaderboard centered over everything else
+    fn draw_leaderboard(f: &mut ratatui::Frame, info: client::ui::ClientInfo) {
+        let lines = match info.leaderboard {
+            Some([]) => vec![text::Line::raw("no rated players")],
+            Some(standings) => standings
+                .iter()
+                .enumerate()
+                .map(|(rank, standing)| {
+                    standing_to_line(rank, standing, standing.name == info.name)
+                })
+                .collect(),
+            None => vec![text::Line::raw("loading...")],
+        };
+
+        let [area] = layout::Layout::vertical([layout::Constraint::Length(lines.len() as u16 + 2)])
+            .flex(layout::Flex::Center)
+            .areas(f.area());
+        let [area] = layout::Layout::horizontal([layout::Constraint::Length(35)])
+            .flex(layout::Flex::Center)
+            .areas(area);
+
+        f.render_widget(widgets::Clear, area);
+        f.render_widget(
+            widgets::Paragraph::new(lines).gray().block(
+                widgets::Block::bordered()
+                    .border_type(widgets::BorderType::Thick)
+                    .title("leaderboard"),
+            ),
+            area,
+        );
+    }
+
+    /// Draws the lines at the bottom of the help area
+    fn draw_help(&self, f: &mut ratatui::Frame, help: Vec<text::Line>) {
+        let [area] = layout::Layout::vertical([layout::Constraint::Length(help.len() as u16)])
+            .flex(layout::Flex::End)
+            .areas(self.help);
+
+        f.render_widget(widgets::Paragraph::new(help).centered().gray(), area);
+    }
 
-        f.render_widget(widgets::Paragraph::new(iter).gray(), self.messages);
+    /// Draws the lines centered below the area, as far as they fit
+    fn draw_below(f: &mut ratatui::Frame, area: layout::Rect, lines: Vec<text::Line>) {
+        let below = layout::Rect {
+            x: f.area().x,
+            y: area.bottom(),
+            width: f.area().width,
+            height: lines.len() as u16,
+        }
+        .intersection(f.area());
+        f.render_widget(widgets::Paragraph::new(lines).centered().gray(), below);
     }
 
     fn default_size() -> layout::Size {
@@ -160,6 +515,31 @@ impl<'s> Layout<'s> {
         }
     }
 
+    /// Size of both boards with their labels
+    fn boards_size(scale: u16) -> layout::Size {
+        if scale == 1 {
+            return Layout::default_size();
+        }
+        layout::Size {
+            width: 20 * scale + 3 + 2 * LABEL_WIDTH,
+            height: 5 * scale + 3,
+        }
+    }
+
+    /// Largest scale of the boards, which leaves room to the panels around
+    /// them, or the compact boards
+    fn scale(area: layout::Rect) -> u16 {
+        (2..=MAX_SCALE)
+            .rev()
+            .step_by(2)
+            .find(|&scale| {
+                let size = Layout::boards_size(scale);
+                area.width >= size.width + 2 * FLEET_WIDTH
+                    && area.height >= size.height + 2 * PANEL_HEIGHT
+            })
+            .unwrap_or(1)
+    }
+
     fn ship_placement_size() -> layout::Size {
         layout::Size {
             width: 12,
@@ -208,19 +588,65 @@ impl<'s> Layout<'s> {
 pub struct Tui {
     term: ratatui::DefaultTerminal,
     cursor_pos: (u8, u8),
+    /// the player has pressed the surrender key and needs to confirm
+    confirm_surrender: bool,
+    /// the chat message the player is typing
+    chat_input: Option<String>,
+    /// the leaderboard is shown over the boards
+    show_leaderboard: bool,
+    /// the position the player is typing, like `E7`
+    position_input: Option<String>,
+    /// why the last typed position was not understood
+    position_error: Option<logic::position::ParseError>,
+    /// the board the mouse points at on the current screen
+    board_area: Option<BoardArea>,
+    /// the probability of a ship is drawn over the unknown positions of the opponent
+    show_advisor: bool,
+    /// input of the player, read without blocking the runtime
+    events: event::EventStream,
+}
+
+/// How likely each unknown position of the opponent hides a ship, as the
+/// density strategy of the computer player sees it
+fn advice(info: client::ui::ClientInfo) -> [[u32; 10]; 10] {
+    let mut cells = [[None; 10]; 10];
+    for (y, row) in info.opponent_hit_map.iter().enumerate() {
+        for (x, attack) in row.iter().enumerate() {
+            cells[y][x] = attack.map(|attack| match attack {
+                client::AttackInfo::Hit => ai::density::Cell::Hit,
+                client::AttackInfo::Miss => ai::density::Cell::Miss,
+            });
+        }
+    }
+    for pos in info.opponent_ships.iter().flat_map(|&ship| ship) {
+        cells[pos] = Some(ai::density::Cell::Sunk);
+    }
+    ai::density::density(&cells, &info.remaining_opponent_ships())
 }
 
 impl Tui {
     pub fn init() -> Tui {
+        let term = ratatui::init();
+        // without mouse capture the keyboard still works
+        let _ = crossterm::execute!(io::stdout(), event::EnableMouseCapture);
         Tui {
-            term: ratatui::init(),
+            term,
             cursor_pos: (0, 0),
+            confirm_surrender: false,
+            chat_input: None,
+            show_leaderboard: false,
+            position_input: None,
+            position_error: None,
+            board_area: None,
+            show_advisor: false,
+            events: event::EventStream::new(),
         }
     }
 }
 
 impl Drop for Tui {
     fn drop(&mut self) {
+        let _ = crossterm::execute!(io::stdout(), event::DisableMouseCapture);
         ratatui::restore();
     }
 }
@@ -236,7 +662,7 @@ pub enum Error {
 impl client::UI for Tui {
     type Error = Error;
 
-    fn request_ships(&mut self) -> Result<logic::Ships, Self::Error> {
+    async fn request_ships(&mut self) -> Result<logic::Ships, Self::Error> {
         let mut x = 0u8;
         let mut y = 0u8;
 
@@ -270,6 +696,8 @@ impl client::UI for Tui {
         .unwrap();
 
         loop {
+            let input_lines = self.position_input_lines();
+            let mut board = None;
             self.term.draw(|f| {
                 if Layout::check_term_too_small(f, Layout::ship_placement_size()) {
                     return;
@@ -282,6 +710,10 @@ impl client::UI for Tui {
                     .areas(horizonta_area);
 
                 let block = widgets::Block::bordered().border_type(widgets::BorderType::Thick);
+                board = Some(BoardArea {
+                    inner: block.inner(area),
+                    scale: 1,
+                });
 
                 let canvas = Tui::new_board_canvas(block).paint(|ctx| {
                     // internal variable dirty needs to be set to `true` D:<
@@ -301,41 +733,63 @@ impl client::UI for Tui {
                     ));
                 });
                 f.render_widget(canvas, area);
+                Layout::draw_below(f, area, input_lines);
             })?;
+            self.board_area = board;
 
-            match event::read()? {
+            let mut lift = false;
+            match self.read().await? {
                 event::Event::Key(key) if key.kind == event::KeyEventKind::Press => {
                     match key.code {
+                        code if self.position_input.is_some() => {
+                            if let Some(pos) = self.edit_position_input(code) {
+                                (x, y) = pos.to_coords();
+                            }
+                        }
+                        KeyCode::Char('g') => self.position_input = Some(String::new()),
                         KeyCode::Char('a') | KeyCode::Left if x > 0 => x -= 1,
                         KeyCode::Char('d') | KeyCode::Right if x < 9 => x += 1,
                         KeyCode::Char('w') | KeyCode::Up if y > 0 => y -= 1,
                         KeyCode::Char('s') | KeyCode::Down if y < 9 => y += 1,
-                        KeyCode::Char(' ') => {
-                            let pos = logic::Position::try_from_coords((x, y)).unwrap();
-                            for (idx, ship) in ships.into_iter().enumerate() {
-                                if ship.into_iter().any(|p| p == pos) {
-                                    ships = self.place_ship(ships, idx, &mut x, &mut y)?;
-                                    break;
-                                }
-                            }
-                        }
+                        KeyCode::Char(' ') => lift = true,
                         KeyCode::Enter => return Ok(ships),
                         KeyCode::Char('q') => return Err(Error::PlayerInterrupt),
                         _ => {}
                     }
                 }
+                event::Event::Mouse(mouse) => {
+                    if let Some(pos) = self
+                        .board_area
+                        .and_then(|b| b.position(mouse.column, mouse.row, (x, y)))
+                    {
+                        (x, y) = pos.to_coords();
+                        lift = mouse.kind == MouseEventKind::Down(MouseButton::Left);
+                    }
+                }
                 _ => {}
             }
+
+            if lift {
+                let pos = logic::Position::try_from_coords((x, y)).unwrap();
+                for (idx, ship) in ships.into_iter().enumerate() {
+                    if ship.into_iter().any(|p| p == pos) {
+                        ships = self.place_ship(ships, idx, &mut x, &mut y).await?;
+                        break;
+                    }
+                }
+            }
         }
     }
 
-    fn request_target(
+    async fn request_target(
         &mut self,
-        info: client::ui::ClientInfo,
-    ) -> Result<logic::Position, Self::Error> {
-        let (mut x, mut y) = self.cursor_pos;
-
+        info: client::ui::ClientInfo<'_>,
+    ) -> Result<client::ui::Target, Self::Error> {
         loop {
+            // the cursor is kept in `self`, as the request gets cancelled on every change
+            let (mut x, mut y) = self.cursor_pos;
+            let input_lines = self.position_input_lines();
+            let mut board = None;
             self.term.draw(|f| {
                 if Layout::check_term_too_small(f, Layout::default_size()) {
                     return;
@@ -343,6 +797,10 @@ impl client::UI for Tui {
 
                 let mut layout = Layout::generate(f.area());
                 layout.opponent_board_border = layout.opponent_board_border.title("sel. targ.");
+                board = Some(BoardArea {
+                    inner: layout.opponent_board_border.inner(layout.opponent_board),
+                    scale: layout.scale,
+                });
 
                 layout.paint_client_board(f, |ctx| {
                     ctx.draw_ext_batch(
@@ -356,6 +814,9 @@ impl client::UI for Tui {
                 });
 
                 layout.paint_opponent_board(f, |ctx| {
+                    if self.show_advisor {
+                        ctx.draw_ext(&advice(info));
+                    }
                     ctx.draw_ext(info.opponent_hit_map);
                     ctx.draw_ext_batch(
                         info.opponent_ships
@@ -369,82 +830,212 @@ impl client::UI for Tui {
                     ));
                 });
 
-                layout.draw_messages(f, info.messages);
+                layout.draw_messages(f, info.messages, self.chat_input.as_deref());
+                layout.draw_status(f, info);
+                layout.draw_fleet(f, info);
+                layout.draw_opponent_fleet(f, info);
+                if self.confirm_surrender {
+                    layout.draw_help(f, surrender_prompt());
+                } else {
+                    layout.draw_help(f, input_lines);
+                }
+                if self.show_leaderboard {
+                    Layout::draw_leaderboard(f, info);
+                }
             })?;
+            self.board_area = board;
 
-            match event::read()? {
+            match self.read().await? {
                 event::Event::Key(key) if key.kind == event::KeyEventKind::Press => {
                     match key.code {
+                        code if self.chat_input.is_some() => {
+                            if let Some(chat) = self.edit_chat_input(code) {
+                                return Ok(client::ui::Target::Command(client::ui::Command::Chat(
+                                    chat,
+                                )));
+                            }
+                        }
+                        code if self.position_input.is_some() => {
+                            if let Some(pos) = self.edit_position_input(code) {
+                                (x, y) = pos.to_coords();
+                                if info.opponent_hit_map[pos].is_none() {
+                                    self.cursor_pos = (x, y);
+                                    return Ok(client::ui::Target::Position(pos));
+                                }
+                            }
+                        }
+                        KeyCode::Char('g') => self.position_input = Some(String::new()),
                         KeyCode::Char('a') | KeyCode::Left if x > 0 => x -= 1,
                         KeyCode::Char('d') | KeyCode::Right if x < 9 => x += 1,
                         KeyCode::Char('w') | KeyCode::Up if y > 0 => y -= 1,
                         KeyCode::Char('s') | KeyCode::Down if y < 9 => y += 1,
+                        KeyCode::Char('t') => self.chat_input = Some(String::new()),
+                        KeyCode::Char('l') => {
+                            self.show_leaderboard = !self.show_leaderboard;
+                            if self.show_leaderboard {
+                                return Ok(client::ui::Target::Command(
+                                    client::ui::Command::Leaderboard,
+                                ));
+                            }
+                        }
+                        KeyCode::Char('h') => self.show_advisor = !self.show_advisor,
                         KeyCode::Char(' ') => {
                             let pos = logic::Position::try_from_coords((x, y)).unwrap();
                             if info.opponent_hit_map[pos].is_none() {
-                                self.cursor_pos = (x, y);
-                                return Ok(pos);
+                                return Ok(client::ui::Target::Position(pos));
                             }
                         }
+                        KeyCode::Char('f') => self.confirm_surrender = true,
+                        KeyCode::Char('y') if self.confirm_surrender => {
+                            self.confirm_surrender = false;
+                            return Ok(client::ui::Target::Command(client::ui::Command::Surrender));
+                        }
+                        KeyCode::Char('n') | KeyCode::Esc => self.confirm_surrender = false,
                         KeyCode::Char('q') => return Err(Error::PlayerInterrupt),
                         _ => {}
                     }
                 }
+                // the cursor follows the mouse, a click fires at it
+                event::Event::Mouse(mouse) => {
+                    if let Some(pos) = self
+                        .board_area
+                        .and_then(|b| b.position(mouse.column, mouse.row, (x, y)))
+                    {
+                        (x, y) = pos.to_coords();
+                        if mouse.kind == MouseEventKind::Down(MouseButton::Left)
+                            && info.opponent_hit_map[pos].is_none()
+                        {
+                            self.cursor_pos = (x, y);
+                            return Ok(client::ui::Target::Position(pos));
+                        }
+                    }
+                }
                 _ => {}
             }
+            self.cursor_pos = (x, y);
         }
     }
 
-    fn display_board(&mut self, info: client::ui::ClientInfo) -> Result<(), Self::Error> {
-        self.term.draw(|f| {
-            if Layout::check_term_too_small(f, Layout::default_size()) {
-                return;
+    fn display_board(
+        &mut self,
+        info: client::ui::ClientInfo,
+        _: &client::ClientEvent,
+    ) -> Result<(), Self::Error> {
+        self.draw_board(info)
+    }
+
+    async fn next_command(
+        &mut self,
+        info: client::ui::ClientInfo<'_>,
+    ) -> Result<client::ui::Command, Self::Error> {
+        loop {
+            match self.read().await? {
+                event::Event::Key(key) if key.kind == event::KeyEventKind::Press => {
+                    match key.code {
+                        code if self.chat_input.is_some() => {
+                            if let Some(chat) = self.edit_chat_input(code) {
+                                return Ok(client::ui::Command::Chat(chat));
+                            }
+                        }
+                        KeyCode::Char('t') => self.chat_input = Some(String::new()),
+                        KeyCode::Char('l') => {
+                            self.show_leaderboard = !self.show_leaderboard;
+                            if self.show_leaderboard {
+                                return Ok(client::ui::Command::Leaderboard);
+                            }
+                        }
+                        KeyCode::Char('f') => self.confirm_surrender = true,
+                        KeyCode::Char('y') if self.confirm_surrender => {
+                            self.confirm_surrender = false;
+                            return Ok(client::ui::Command::Surrender);
+                        }
+                        KeyCode::Char('n') | KeyCode::Esc => self.confirm_surrender = false,
+                        KeyCode::Char('q') => return Err(Error::PlayerInterrupt),
+                        _ => continue,
+                    }
+                }
+                event::Event::Resize(..) => {}
+                _ => continue,
             }
+            self.draw_board(info)?;
+        }
+    }
 
-            let layout = Layout::generate(f.area());
+    async fn request_rematch(
+        &mut self,
+        info: client::ui::ClientInfo<'_>,
+    ) -> Result<bool, Self::Error> {
+        self.draw_outcome(
+            info,
+            info.outcome.unwrap_or_default(),
+            vec![text::Line::from(vec![
+                text::Span::raw("r").bold(),
+                text::Span::raw(" rematch  "),
+                text::Span::raw("q").bold(),
+                text::Span::raw(" quit"),
+            ])],
+        )?;
 
-            layout.paint_client_board(f, |ctx| {
-                ctx.draw_ext_batch(
-                    info.ships
-                        .into_iter()
-                        .cloned()
-                        .enumerate()
-                        .map(|(i, ship)| (ship, SHIP_COLORS[i])),
-                );
-                ctx.draw_ext(info.client_hit_map);
-            });
+        loop {
+            match self.read().await? {
+                event::Event::Key(key) if key.kind == event::KeyEventKind::Press => {
+                    match key.code {
+                        KeyCode::Char('r') => return Ok(true),
+                        KeyCode::Char('q') => return Ok(false),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
 
-            layout.paint_opponent_board(f, |ctx| {
-                ctx.draw_ext(info.opponent_hit_map);
-                ctx.draw_ext_batch(
-                    info.opponent_ships
-                        .into_iter()
-                        .cloned()
-                        .zip(std::iter::repeat(style::Color::Red)),
-                );
-            });
+    async fn display_victory(
+        &mut self,
+        info: client::ui::ClientInfo<'_>,
+    ) -> Result<(), Self::Error> {
+        self.draw_outcome(info, true, vec![])?;
 
-            layout.draw_messages(f, info.messages);
-        })?;
+        loop {
+            match self.read().await? {
+                event::Event::Key(key) if key.kind == event::KeyEventKind::Press => {
+                    if let KeyCode::Char('q') = key.code {
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
 
-        while event::poll(std::time::Duration::from_secs(0))? {
-            match event::read()? {
+    async fn display_loss(&mut self, info: client::ui::ClientInfo<'_>) -> Result<(), Self::Error> {
+        self.draw_outcome(info, false, vec![])?;
+
+        loop {
+            match self.read().await? {
                 event::Event::Key(key) if key.kind == event::KeyEventKind::Press => {
                     match key.code {
-                        KeyCode::Char('q') => return Err(Error::PlayerInterrupt),
+                        KeyCode::Char('q') => return Ok(()),
                         _ => {}
                     }
                 }
                 _ => {}
             }
         }
-
-        Ok(())
     }
+}
 
-    fn display_victory(&mut self, info: client::ui::ClientInfo) -> Result<(), Self::Error> {
-        const MESSAGE: &str = "V I C T O R Y";
+impl Tui {
+    /// Waits for the next input of the player
+    async fn read(&mut self) -> Result<event::Event, Error> {
+        match self.events.next().await {
+            Some(event) => Ok(event?),
+            None => Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+        }
+    }
 
+    /// Draws both boards while the opponent is shooting or no match runs
+    fn draw_board(&mut self, info: client::ui::ClientInfo) -> Result<(), Error> {
         self.term.draw(|f| {
             if Layout::check_term_too_small(f, Layout::default_size()) {
                 return;
@@ -467,48 +1058,103 @@ impl client::UI for Tui {
                 ctx.draw_ext(info.opponent_hit_map);
                 ctx.draw_ext_batch(
                     info.opponent_ships
-                        .into_iter()
+                        .iter()
                         .cloned()
                         .zip(std::iter::repeat(style::Color::Red)),
                 );
             });
 
-            layout.draw_messages(f, info.messages);
-
-            let [center_box] = layout::Layout::vertical([layout::Constraint::Length(1)])
-                .flex(layout::Flex::Center)
-                .areas(f.area());
-            let [center_box] = layout::Layout::horizontal([layout::Constraint::Length(
-                (MESSAGE.len() + 2) as u16,
-            )])
-            .flex(layout::Flex::Center)
-            .areas(center_box);
-
-            f.render_widget(
-                widgets::Paragraph::new(MESSAGE)
-                    .bold()
-                    .centered()
-                    .yellow()
-                    .on_white(),
-                center_box,
-            );
+            layout.draw_messages(f, info.messages, self.chat_input.as_deref());
+            layout.draw_status(f, info);
+            layout.draw_fleet(f, info);
+            layout.draw_opponent_fleet(f, info);
+            if self.confirm_surrender {
+                layout.draw_help(f, surrender_prompt());
+            }
+            if self.show_leaderboard {
+                Layout::draw_leaderboard(f, info);
+            }
         })?;
+        Ok(())
+    }
 
-        loop {
-            match event::read()? {
-                event::Event::Key(key) if key.kind == event::KeyEventKind::Press => {
-                    match key.code {
-                        KeyCode::Char('q') => return Ok(()),
-                        _ => {}
-                    }
-                }
-                _ => {}
+    /// Applies the key to the chat input line. Returns the message once the
+    /// player presses enter.
+    fn edit_chat_input(&mut self, code: KeyCode) -> Option<String> {
+        let input = self.chat_input.as_mut()?;
+        match code {
+            KeyCode::Char(c) if input.len() + c.len_utf8() <= client::ui::MAX_CHAT_LEN => {
+                input.push(c)
             }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.chat_input = None,
+            KeyCode::Enter => {
+                return self
+                    .chat_input
+                    .take()
+                    .filter(|chat| !chat.trim().is_empty());
+            }
+            _ => {}
         }
+        None
     }
 
-    fn display_loss(&mut self, info: client::ui::ClientInfo) -> Result<(), Self::Error> {
-        const MESSAGE: &str = "L O S S";
+    /// Applies the key to the position input line. Returns the position once
+    /// the player presses enter and it is understood.
+    fn edit_position_input(&mut self, code: KeyCode) -> Option<logic::Position> {
+        let input = self.position_input.as_mut()?;
+        self.position_error = None;
+        match code {
+            KeyCode::Char(c) if input.len() < 3 => input.push(c.to_ascii_uppercase()),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.position_input = None,
+            KeyCode::Enter => match input.parse() {
+                Ok(pos) => {
+                    self.position_input = None;
+                    return Some(pos);
+                }
+                Err(err) => self.position_error = Some(err),
+            },
+            _ => {}
+        }
+        None
+    }
+
+    /// The position the player is typing, below why the last one was not
+    /// understood
+    fn position_input_lines(&self) -> Vec<text::Line<'static>> {
+        let mut lines = Vec::new();
+        if let Some(err) = &self.position_error {
+            lines.push(text::Line::raw(err.to_string()).light_red());
+        }
+        if let Some(input) = &self.position_input {
+            lines.push(text::Line::from(vec![
+                text::Span::raw("go to ").yellow(),
+                text::Span::raw(input.clone()),
+                text::Span::raw("_").slow_blink(),
+            ]));
+        }
+        lines
+    }
+
+    /// Draws the final boards with a victory or loss banner, and the series
+    /// score followed by `help` in the help area.
+    fn draw_outcome(
+        &mut self,
+        info: client::ui::ClientInfo,
+        victory: bool,
+        mut help: Vec<text::Line>,
+    ) -> io::Result<()> {
+        const VICTORY: &str = "V I C T O R Y";
+        const LOSS: &str = "L O S S";
+
+        if let Some(score) = info.score {
+            help.insert(0, score_to_line(score));
+        }
 
         self.term.draw(|f| {
             if Layout::check_term_too_small(f, Layout::default_size()) {
@@ -538,42 +1184,35 @@ impl client::UI for Tui {
                 );
             });
 
-            layout.draw_messages(f, info.messages);
+            layout.draw_messages(f, info.messages, None);
+            layout.draw_fleet(f, info);
+            layout.draw_opponent_fleet(f, info);
+            layout.draw_help(f, help);
 
+            let message = if victory { VICTORY } else { LOSS };
             let [center_box] = layout::Layout::vertical([layout::Constraint::Length(1)])
                 .flex(layout::Flex::Center)
                 .areas(f.area());
             let [center_box] = layout::Layout::horizontal([layout::Constraint::Length(
-                (MESSAGE.len() + 2) as u16,
+                (message.len() + 2) as u16,
             )])
             .flex(layout::Flex::Center)
             .areas(center_box);
 
+            let banner = widgets::Paragraph::new(message).bold().centered();
             f.render_widget(
-                widgets::Paragraph::new(MESSAGE)
-                    .bold()
-                    .centered()
-                    .cyan()
-                    .on_white(),
+                if victory {
+                    banner.yellow().on_white()
+                } else {
+                    banner.cyan().on_white()
+                },
                 center_box,
             );
         })?;
 
-        loop {
-            match event::read()? {
-                event::Event::Key(key) if key.kind == event::KeyEventKind::Press => {
-                    match key.code {
-                        KeyCode::Char('q') => return Ok(()),
-                        _ => {}
-                    }
-                }
-                _ => {}
-            }
-        }
+        Ok(())
     }
-}
 
-impl Tui {
     fn new_board_canvas<'a, F: Fn(&mut canvas::Context)>(
         block: widgets::Block<'a>,
     ) -> canvas::Canvas<'a, F> {
@@ -582,7 +1221,7 @@ impl Tui {
             .marker(symbols::Marker::HalfBlock)
     }
 
-    fn place_ship(
+    async fn place_ship(
         &mut self,
         ships: logic::Ships,
         ship_idx: usize,
@@ -601,9 +1240,15 @@ impl Tui {
 
         loop {
             let mut check = false;
-            match event::read()? {
+            match self.read().await? {
                 event::Event::Key(key) if key.kind == event::KeyEventKind::Press => {
                     match key.code {
+                        code if self.position_input.is_some() => {
+                            if let Some(pos) = self.edit_position_input(code) {
+                                (*x, *y) = pos.to_coords();
+                            }
+                        }
+                        KeyCode::Char('g') => self.position_input = Some(String::new()),
                         KeyCode::Char('a') | KeyCode::Left => *x = x.saturating_sub(1),
                         KeyCode::Char('d') | KeyCode::Right => *x += 1,
                         KeyCode::Char('w') | KeyCode::Up => *y = y.saturating_sub(1),
@@ -614,6 +1259,22 @@ impl Tui {
                         _ => {}
                     }
                 }
+                // the ship follows the mouse and is placed where the button is released
+                event::Event::Mouse(mouse) => {
+                    if let Some(pos) = self
+                        .board_area
+                        .and_then(|b| b.position(mouse.column, mouse.row, (*x, *y)))
+                    {
+                        (*x, *y) = pos.to_coords();
+                    }
+                    match mouse.kind {
+                        MouseEventKind::Up(MouseButton::Left) => check = true,
+                        MouseEventKind::Down(MouseButton::Right)
+                        | MouseEventKind::ScrollUp
+                        | MouseEventKind::ScrollDown => horizontal ^= true,
+                        _ => {}
+                    }
+                }
 
                 _ => {}
             }
@@ -659,6 +1320,8 @@ impl Tui {
                 }
             }
 
+            let input_lines = self.position_input_lines();
+            let mut board = None;
             self.term.draw(|f| {
                 if Layout::check_term_too_small(f, Layout::ship_placement_size()) {
                     return;
@@ -676,6 +1339,10 @@ impl Tui {
                     } else {
                         style::Color::Red
                     }));
+                board = Some(BoardArea {
+                    inner: block.inner(area),
+                    scale: 1,
+                });
 
                 let canvas = Tui::new_board_canvas(block).paint(|ctx| {
                     // internal variable dirty needs to be set to `true` D:<
@@ -696,7 +1363,78 @@ impl Tui {
                     ));
                 });
                 f.render_widget(canvas, area);
+                Layout::draw_below(f, area, input_lines);
             })?;
+            self.board_area = board;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A board drawn at `scale`, its border at column 2 and row 1
+    fn board_area(scale: u16) -> BoardArea {
+        BoardArea {
+            inner: layout::Rect::new(3, 2, 10 * scale, 5 * scale),
+            scale,
+        }
+    }
+
+    fn position(x: u8, y: u8) -> Option<logic::Position> {
+        logic::Position::try_from_coords((x, y)).ok()
+    }
+
+    /// Cells of the border, and just outside of it
+    fn outside(scale: u16) -> [(u16, u16); 6] {
+        let (right, bottom) = (3 + 10 * scale, 2 + 5 * scale);
+        [
+            (2, 2),
+            (3, 1),
+            (right, 2),
+            (3, bottom),
+            (right, bottom),
+            (0, 0),
+        ]
+    }
+
+    #[test]
+    fn board_area_compact() {
+        let area = board_area(1);
+        // a cell shows two positions, the one nearer to the cursor is taken
+        assert_eq!(area.position(3, 2, (0, 0)), position(0, 0));
+        assert_eq!(area.position(3, 2, (0, 9)), position(0, 1));
+        assert_eq!(area.position(8, 4, (5, 4)), position(5, 4));
+        assert_eq!(area.position(8, 4, (5, 5)), position(5, 5));
+        assert_eq!(area.position(12, 6, (0, 0)), position(9, 8));
+        assert_eq!(area.position(12, 6, (9, 9)), position(9, 9));
+        for (column, row) in outside(1) {
+            assert_eq!(area.position(column, row, (0, 0)), None);
+        }
+    }
+
+    #[test]
+    fn board_area_scaled() {
+        for scale in [2, 4] {
+            let area = board_area(scale);
+            for (x, y) in (0..10).flat_map(|x| (0..10).map(move |y| (x, y))) {
+                // every cell showing the position maps back to it
+                for column in 0..scale {
+                    for row in 0..scale / 2 {
+                        let cell = (
+                            3 + x as u16 * scale + column,
+                            2 + y as u16 * scale / 2 + row,
+                        );
+                        for cursor in [(0, 0), (9, 9)] {
+                            assert_eq!(area.position(cell.0, cell.1, cursor), position(x, y));
+                        }
+                    }
+                }
+            }
+            for (column, row) in outside(scale) {
+                assert_eq!(area.position(column, row, (0, 0)), None);
+            }
         }
     }
 }